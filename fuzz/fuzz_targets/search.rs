@@ -0,0 +1,29 @@
+#![no_main]
+
+use aho_corasick::Automation;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (Vec<Vec<u8>>, Vec<u8>)| {
+    let (patterns, haystack) = input;
+
+    let automation = Automation::build(patterns.iter().map(|pattern| pattern.as_slice()));
+    assert!(automation.verify_failure_links());
+
+    let mut search = automation.search();
+    let mut anchored = automation.search();
+
+    for b in &haystack {
+        for &output in search.next(b) {
+            assert!(output < automation.num_patterns());
+        }
+
+        for &output in anchored.next_anchored(b) {
+            assert!(output < automation.num_patterns());
+        }
+    }
+
+    for (output, end) in automation.find_iter_bytes(&haystack) {
+        assert!(output < automation.num_patterns());
+        assert!(haystack[..end].ends_with(patterns[output].as_slice()));
+    }
+});
@@ -0,0 +1,140 @@
+use core::fmt;
+
+use crate::{Automation, Pattern};
+
+/// Chars whose case can be folded for case-insensitive matching.
+pub trait CaseFold {
+    fn fold_ascii_case(&self) -> Self;
+}
+
+impl CaseFold for char {
+    fn fold_ascii_case(&self) -> Self {
+        self.to_ascii_lowercase()
+    }
+}
+
+impl CaseFold for u8 {
+    fn fold_ascii_case(&self) -> Self {
+        self.to_ascii_lowercase()
+    }
+}
+
+/// Pattern wrapper matching ASCII letters regardless of their case.
+///
+/// Both the pattern and the searched input are folded to lowercase.
+/// Only `A`-`Z` are folded, so non-ASCII chars are left untouched
+/// and byte offsets of matches stay the same as in the original input.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct AsciiCaseInsensitive<P>(pub P);
+
+impl<P: Pattern> Pattern for AsciiCaseInsensitive<P>
+where
+    P::Char: CaseFold,
+{
+    type Char = P::Char;
+
+    fn iter(&self) -> impl Iterator<Item = Self::Char> {
+        self.0.iter().map(|c| c.fold_ascii_case())
+    }
+
+    fn fold(c: &Self::Char) -> Option<Self::Char> {
+        Some(c.fold_ascii_case())
+    }
+}
+
+impl<P: fmt::Display> fmt::Display for AsciiCaseInsensitive<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<P: Pattern> Automation<AsciiCaseInsensitive<P>>
+where
+    P::Char: CaseFold,
+{
+    /// Builds an automation matching the patterns case-insensitively,
+    /// see [`AsciiCaseInsensitive`].
+    pub fn build_ascii_case_insensitive(items: impl Iterator<Item = P>) -> Self {
+        Self::build(items.map(AsciiCaseInsensitive))
+    }
+}
+
+/// Pattern wrapper matching chars regardless of their case, for all of Unicode.
+///
+/// Both the pattern and the searched input are folded char by char with
+/// [`char::to_lowercase`], so e.g. `"Ä"` matches `"ä"` and `"ÉTÉ"` matches `"été"`.
+///
+/// Some chars lowercase to several chars, e.g. `'İ'` to `"i̇"`. Folding them would
+/// change the number of chars, so they are kept as is and only match themselves.
+/// This keeps the folding one char to one char, which means that the offsets
+/// of matches always refer to the original input. Likewise, no char ever folds
+/// into several ones, so `"STRASSE"` does not match `"straße"`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct UnicodeCaseInsensitive<P>(pub P);
+
+impl<P: Pattern<Char = char>> Pattern for UnicodeCaseInsensitive<P> {
+    type Char = char;
+
+    fn iter(&self) -> impl Iterator<Item = Self::Char> {
+        self.0.iter().map(fold_unicode_case)
+    }
+
+    fn fold(c: &Self::Char) -> Option<Self::Char> {
+        Some(fold_unicode_case(*c))
+    }
+}
+
+impl<P: fmt::Display> fmt::Display for UnicodeCaseInsensitive<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<P: Pattern<Char = char>> Automation<UnicodeCaseInsensitive<P>> {
+    /// Builds an automation matching the patterns case-insensitively,
+    /// see [`UnicodeCaseInsensitive`].
+    pub fn build_unicode_case_insensitive(items: impl Iterator<Item = P>) -> Self {
+        Self::build(items.map(UnicodeCaseInsensitive))
+    }
+}
+
+// Lowercases `c`, unless it lowercases to several chars.
+fn fold_unicode_case(c: char) -> char {
+    let mut lower = c.to_lowercase();
+
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn ascii_case_insensitive() {
+        let automation = Automation::build_ascii_case_insensitive(["Hello", "ä"].into_iter());
+        let matches: Vec<_> = automation.find_iter("hELLo world Ä ä").collect();
+
+        // Only ASCII letters are folded, so `"ä"` does not match `"Ä"`.
+        assert_eq!(matches, [(0, 5), (1, 17)]);
+    }
+
+    #[test]
+    fn unicode_case_insensitive() {
+        let automation =
+            Automation::build_unicode_case_insensitive(["ÉTÉ", "straße", "İ"].into_iter());
+        let matches: Vec<_> = automation.find_iter("un été STRASSE İi Straße").collect();
+
+        // `'İ'` only matches itself and `"SS"` is not folded into `'ß'`.
+        assert_eq!(matches, [(0, 8), (2, 19), (1, 28)]);
+        assert!(!automation.is_match("i"));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{map::Key, Automation, AutomationNode};
+use alloc::{vec, vec::Vec};
+
+/// Pattern made of char classes, each of which matches any single one of its members,
+/// e.g. `[aeiou]t` for any vowel followed by `t`.
+///
+/// Single chars are classes with one member.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ClassPattern<C>(pub Vec<Vec<C>>);
+
+impl ClassPattern<char> {
+    /// Parses a pattern in which the chars between `[` and `]` form a class,
+    /// and every other char is a class of its own, e.g. `"[aeiou]t"`.
+    ///
+    /// Returns `None` if a bracket is unbalanced or a class is empty.
+    /// There are no ranges or escapes, so `[` and `]` can only be matched
+    /// by building the pattern by hand.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut classes = Vec::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '[' => {
+                    let mut class = Vec::new();
+
+                    loop {
+                        match chars.next()? {
+                            ']' => break,
+                            '[' => return None,
+                            c => class.push(c),
+                        }
+                    }
+
+                    if class.is_empty() {
+                        return None;
+                    }
+                    classes.push(class);
+                }
+                ']' => return None,
+                c => classes.push(vec![c]),
+            }
+        }
+
+        Some(ClassPattern(classes))
+    }
+}
+
+impl<C: Key + Clone> Automation<Vec<C>> {
+    /// Builds an automation from patterns made of char classes,
+    /// assigning output IDs in the order the patterns come in.
+    ///
+    /// Every class fans out into a goto transition per member, so a pattern takes
+    /// as many trie paths as it has combinations of members, all reporting its output ID.
+    /// The nodes after a class are not shared between its members, since failure links
+    /// are only well defined if every node has a single trie path, which makes this
+    /// only suited for small classes.
+    ///
+    /// A pattern with a class of several members ends at several nodes,
+    /// so [`Automation::patterns`] cannot reconstruct it.
+    pub fn build_classes(items: impl Iterator<Item = ClassPattern<C>>) -> Self {
+        let mut automation = Self::new();
+
+        for item in items {
+            automation.insert_class(item);
+        }
+        automation.build_failure();
+
+        automation
+    }
+
+    fn insert_class(&mut self, item: ClassPattern<C>) -> usize {
+        let len = item.0.len();
+        let mut ends = vec![0];
+
+        for (depth, class) in (1..).zip(item.0) {
+            let mut next_ends = Vec::new();
+
+            for &node_idx in &ends {
+                for c in &class {
+                    let next = match self.nodes[node_idx].enter_child(c) {
+                        Some(next) => next,
+                        None => {
+                            let next = self.nodes.len();
+                            self.nodes.push(AutomationNode::new(depth));
+                            self.nodes[node_idx].add_child(c.clone(), next, &self.hasher);
+                            next
+                        }
+                    };
+
+                    if !next_ends.contains(&next) {
+                        next_ends.push(next);
+                    }
+                }
+            }
+
+            ends = next_ends;
+        }
+
+        let output_idx = self.output_cnt;
+
+        if ends.len() > 1 {
+            self.class_outputs.push(output_idx);
+        }
+
+        // Other patterns may end at the same nodes, in which case all of them are reported.
+        for node_idx in ends {
+            self.nodes[node_idx].add_output(output_idx);
+        }
+
+        self.pattern_lens.push(len);
+        self.values.push(());
+        self.output_cnt += 1;
+
+        output_idx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_matches_any_member() {
+        let pattern = ClassPattern::parse("[aeiou]t").unwrap();
+        let automation = Automation::build_classes([pattern].into_iter());
+
+        assert!(automation.is_match("at"));
+        assert!(automation.is_match("ot"));
+        assert!(!automation.is_match("bt"));
+        assert_eq!(
+            automation.find_iter("bat out").collect::<Vec<_>>(),
+            [(0, 3), (0, 7)]
+        );
+    }
+
+    #[test]
+    fn plain_pattern_is_not_a_duplicate_of_a_class() {
+        let pattern = ClassPattern::parse("[ab]t").unwrap();
+        let mut automation = Automation::build_classes([pattern].into_iter());
+        automation.add_item(vec!['a', 't']);
+        automation.finalize();
+
+        assert_eq!(automation.duplicate_of(1), None);
+        assert_eq!(
+            automation.find_iter("bt at").collect::<Vec<_>>(),
+            [(0, 2), (0, 5), (1, 5)]
+        );
+
+        // A class with a single member is the same as a plain char.
+        let pattern = ClassPattern::parse("[a]t").unwrap();
+        let mut automation = Automation::build_classes([pattern].into_iter());
+        automation.add_item(vec!['a', 't']);
+        assert_eq!(automation.duplicate_of(1), Some(0));
+    }
+
+    #[test]
+    fn class_patterns_are_not_reconstructed() {
+        let patterns = ["[ao]t", "xy", "[b]c"].map(|pattern| ClassPattern::parse(pattern).unwrap());
+        let automation = Automation::build_classes(patterns.into_iter());
+
+        assert_eq!(
+            automation.patterns(),
+            [None, Some(vec!['x', 'y']), Some(vec!['b', 'c'])]
+        );
+    }
+}
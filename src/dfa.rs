@@ -0,0 +1,138 @@
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::{Automation, HashBuilder, Pattern};
+
+const ALPHABET_LEN: usize = 256;
+
+/// Byte automation with a precomputed transition for every state and byte.
+///
+/// Unlike [`AutomationSearch`](crate::AutomationSearch), searching never walks
+/// failure links, every byte is a single table lookup.
+///
+/// States are stored as `u32` to keep the table, which has 256 entries per state, compact.
+#[derive(Clone)]
+pub struct Dfa {
+    table: Vec<u32>,
+    outputs: Vec<Vec<usize>>,
+}
+
+impl Dfa {
+    pub fn search(&self) -> DfaSearch<'_> {
+        DfaSearch::new(self)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct DfaSearch<'a> {
+    dfa: &'a Dfa,
+    current: usize,
+}
+
+impl<'a> DfaSearch<'a> {
+    pub fn new(dfa: &'a Dfa) -> Self {
+        Self { dfa, current: 0 }
+    }
+
+    /// Feeds the next byte and returns the outputs of all patterns ending at it.
+    pub fn next(&mut self, b: &u8) -> &[usize] {
+        self.current = self.dfa.table[self.current * ALPHABET_LEN + *b as usize] as usize;
+        &self.dfa.outputs[self.current]
+    }
+
+    /// Returns the search to the root state.
+    pub fn reset(&mut self) {
+        self.current = 0;
+    }
+}
+
+impl<P: Pattern<Char = u8>, V, S: HashBuilder> Automation<P, V, S> {
+    /// Materializes the transitions of every node for every byte into a [`Dfa`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the automation has more than `u32::MAX` nodes.
+    pub fn to_dfa(&self) -> Dfa {
+        assert!(
+            u32::try_from(self.nodes.len()).is_ok(),
+            "too many nodes for a dfa: {}",
+            self.nodes.len()
+        );
+
+        let mut table = vec![0; self.nodes.len() * ALPHABET_LEN];
+
+        // Use BFS so that the failure node, being shallower,
+        // always has its row filled before the nodes pointing to it.
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+
+        while let Some(node_index) = queue.pop_front() {
+            let node = &self.nodes[node_index];
+
+            for b in 0..=u8::MAX {
+                let c = P::fold(&b).unwrap_or(b);
+                let next = match node.enter_child(&c) {
+                    Some(next) => next as u32,
+                    None if node_index == 0 => 0,
+                    None => table[node.failure * ALPHABET_LEN + c as usize],
+                };
+
+                table[node_index * ALPHABET_LEN + b as usize] = next;
+            }
+
+            queue.extend(node.goto.values().copied());
+        }
+
+        Dfa {
+            table,
+            outputs: self.nodes.iter().map(|node| node.outputs.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small LCG, so the data is random but the same on every run.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) as usize % bound
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            // A small alphabet makes patterns share prefixes and suffixes.
+            (0..len).map(|_| b'a' + self.next(4) as u8).collect()
+        }
+    }
+
+    #[test]
+    fn dfa_matches_automation() {
+        let mut rng = Lcg(42);
+
+        for _ in 0..50 {
+            let patterns: Vec<Vec<u8>> = (0..1 + rng.next(10))
+                .map(|_| {
+                    let len = 1 + rng.next(5);
+                    rng.bytes(len)
+                })
+                .collect();
+            let haystack = rng.bytes(200);
+
+            let automation =
+                Automation::build(patterns.as_slice().iter().map(|pattern| pattern.as_slice()));
+            let dfa = automation.to_dfa();
+            let mut expected = automation.search();
+            let mut search = dfa.search();
+
+            for b in &haystack {
+                assert_eq!(search.next(b), expected.next(b), "patterns {:?}", patterns);
+            }
+        }
+    }
+}
@@ -1,3 +1,9 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
 use crate::{Automation, Pattern};
 
 #[derive(Clone)]
@@ -8,6 +14,7 @@ struct AutomationDumpNode {
     outputs: Vec<usize>,
 }
 
+#[cfg_attr(not(any(feature = "dot", feature = "mermaid")), allow(dead_code))]
 #[derive(Clone)]
 enum EdTarget {
     Goto(usize),
@@ -16,23 +23,55 @@ enum EdTarget {
 
 type EdgeDesc = (usize, EdTarget);
 
+#[cfg_attr(not(any(feature = "dot", feature = "mermaid")), allow(dead_code))]
 pub struct AutomationDump {
     nodes: Vec<AutomationDumpNode>,
     edges: Vec<EdgeDesc>,
+    pattern_lens: Vec<usize>,
+    patterns: Option<Vec<String>>,
+    output_labels: Vec<String>,
 }
 
 impl AutomationDump {
-    pub fn create<P>(automation: &Automation<P>) -> Self
+    pub fn create<P, V, S>(automation: &Automation<P, V, S>) -> Self
     where
-        P: Pattern + ToString,
+        P: Pattern,
         P::Char: ToString,
+    {
+        let pattern_lens = &automation.pattern_lens;
+
+        let mut dump = Self::create_with(
+            automation,
+            |c| c.to_string(),
+            |output| format!("{} ({})", output, pattern_lens[output]),
+        );
+        dump.patterns = automation
+            .patterns
+            .as_ref()
+            .map(|patterns| patterns.iter().map(pattern_to_string).collect());
+        dump
+    }
+
+    /// Same as [`AutomationDump::create`], but labels nodes by their char with `node_fmt`
+    /// and outputs by their ID with `output_fmt`, so `Char` needs no `ToString` impl.
+    ///
+    /// Retained patterns are left out, since they cannot be rendered without it.
+    pub fn create_with<P, V, S, FC, FN>(
+        automation: &Automation<P, V, S>,
+        mut node_fmt: FC,
+        output_fmt: FN,
+    ) -> Self
+    where
+        P: Pattern,
+        FC: FnMut(&P::Char) -> String,
+        FN: FnMut(usize) -> String,
     {
         let mut nodes: Vec<AutomationDumpNode> = automation
             .nodes
             .iter()
             .map(|x| AutomationDumpNode {
                 node: String::new(),
-                goto: x.goto.values().map(|&x| x).collect(),
+                goto: x.goto.values().copied().collect(),
                 failure: x.failure,
                 outputs: x.outputs.clone(),
             })
@@ -41,18 +80,121 @@ impl AutomationDump {
 
         for (idx, node) in automation.nodes.iter().enumerate() {
             for (c, &next_node) in node.goto.iter() {
-                nodes[next_node].node = c.to_string();
+                nodes[next_node].node = node_fmt(c);
                 edges.push((idx, EdTarget::Goto(next_node)));
             }
             edges.push((idx, EdTarget::Failure(node.failure)));
         }
 
-        Self { nodes, edges }
+        Self {
+            nodes,
+            edges,
+            pattern_lens: automation.pattern_lens.clone(),
+            patterns: None,
+            output_labels: (0..automation.output_cnt).map(output_fmt).collect(),
+        }
+    }
+}
+
+// Renders a pattern as the concatenation of its chars,
+// so that it works for any `Char`, not only for string patterns.
+fn pattern_to_string<P: Pattern>(pattern: &P) -> String
+where
+    P::Char: ToString,
+{
+    pattern.iter().map(|c| c.to_string()).collect()
+}
+
+mod jsondump {
+    use alloc::string::String;
+    use core::fmt::Write;
+
+    use super::AutomationDump;
+
+    impl AutomationDump {
+        /// Renders the automation as JSON.
+        ///
+        /// Every node is an object with its `index`, `label`, `goto` targets,
+        /// `failure` link and `outputs`. The nodes are followed by the `pattern_lens`
+        /// table and the `patterns` string table, which is `null` unless
+        /// the automation retains its patterns.
+        pub fn to_json(&self) -> String {
+            let mut out = String::from("{\"nodes\":[");
+
+            for (idx, node) in self.nodes.iter().enumerate() {
+                if idx != 0 {
+                    out.push(',');
+                }
+
+                write!(out, "{{\"index\":{},\"label\":", idx).unwrap();
+                write_str(&mut out, &node.node);
+                out.push_str(",\"goto\":");
+                write_list(&mut out, &node.goto);
+                write!(out, ",\"failure\":{},\"outputs\":", node.failure).unwrap();
+                write_list(&mut out, &node.outputs);
+                out.push('}');
+            }
+
+            out.push_str("],\"pattern_lens\":");
+            write_list(&mut out, &self.pattern_lens);
+            out.push_str(",\"patterns\":");
+
+            match &self.patterns {
+                Some(patterns) => {
+                    out.push('[');
+
+                    for (idx, pattern) in patterns.iter().enumerate() {
+                        if idx != 0 {
+                            out.push(',');
+                        }
+                        write_str(&mut out, pattern);
+                    }
+
+                    out.push(']');
+                }
+                None => out.push_str("null"),
+            }
+
+            out.push('}');
+            out
+        }
+    }
+
+    fn write_list(out: &mut String, items: &[usize]) {
+        out.push('[');
+
+        for (idx, item) in items.iter().enumerate() {
+            if idx != 0 {
+                out.push(',');
+            }
+            write!(out, "{}", item).unwrap();
+        }
+
+        out.push(']');
+    }
+
+    fn write_str(out: &mut String, s: &str) {
+        out.push('"');
+
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                c => out.push(c),
+            }
+        }
+
+        out.push('"');
     }
 }
 
 #[cfg(feature = "dot")]
 mod dotdump {
+    use alloc::{format, string::String, vec::Vec};
     use std::io;
 
     use super::{AutomationDump, EdTarget};
@@ -86,7 +228,7 @@ mod dotdump {
 
                 for &output in &node.outputs {
                     s.push_str("<br/>");
-                    s.push_str(&output.to_string());
+                    s.push_str(&self.output_labels[output]);
                 }
 
                 s.push_str("</font>");
@@ -128,3 +270,144 @@ mod dotdump {
         }
     }
 }
+
+#[cfg(feature = "mermaid")]
+mod mermaiddump {
+    use alloc::string::String;
+    use core::fmt::Write;
+
+    use super::{AutomationDump, EdTarget};
+
+    impl AutomationDump {
+        /// Renders the automation as a Mermaid `graph TD` diagram,
+        /// with solid goto edges and dashed failure edges.
+        pub fn to_mermaid(&self) -> String {
+            let mut out = String::from("graph TD\n");
+
+            for (idx, node) in self.nodes.iter().enumerate() {
+                let mut label = escape(&node.node);
+
+                for &output in &node.outputs {
+                    write!(label, "<br/>{}", escape(&self.output_labels[output])).unwrap();
+                }
+
+                if label.is_empty() {
+                    label.push(' ');
+                }
+
+                writeln!(out, "    N{}[\"{}\"]", idx, label).unwrap();
+            }
+
+            for (source, target) in &self.edges {
+                match target {
+                    EdTarget::Goto(target) => writeln!(out, "    N{} --> N{}", source, target),
+                    EdTarget::Failure(target) => writeln!(out, "    N{} -.-> N{}", source, target),
+                }
+                .unwrap();
+            }
+
+            out
+        }
+    }
+
+    fn escape(s: &str) -> String {
+        let mut out = String::new();
+
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("#quot;"),
+                '<' => out.push_str("#lt;"),
+                '>' => out.push_str("#gt;"),
+                '#' => out.push_str("#35;"),
+                c => out.push(c),
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn dump_structure() {
+        let automation = Automation::build(["she", "he", "hers"].into_iter());
+        let dump = AutomationDump::create(&automation);
+        let node = |prefix| automation.node_for_prefix(&prefix).unwrap();
+
+        // The trie path of every node, the one of its failure link target, and its outputs.
+        let expected = [
+            ("", "", vec![]),
+            ("s", "", vec![]),
+            ("sh", "h", vec![]),
+            ("she", "he", vec![0, 1]),
+            ("h", "", vec![]),
+            ("he", "", vec![1]),
+            ("her", "", vec![]),
+            ("hers", "s", vec![2]),
+        ];
+        let node_cnt = expected.len();
+        assert_eq!(dump.nodes.len(), node_cnt);
+
+        for (prefix, failure, outputs) in expected {
+            let dumped = &dump.nodes[node(prefix)];
+
+            assert_eq!(
+                dumped.node,
+                prefix.chars().last().map_or(String::new(), String::from)
+            );
+            assert_eq!(dumped.failure, node(failure));
+            assert_eq!(dumped.outputs, outputs);
+        }
+
+        let gotos = dump
+            .edges
+            .iter()
+            .filter(|(_, target)| matches!(target, EdTarget::Goto(_)))
+            .count();
+        assert_eq!(gotos, node_cnt - 1);
+        assert_eq!(dump.edges.len() - gotos, node_cnt);
+    }
+
+    #[test]
+    fn json_parses_back() {
+        let automation = Automation::build_with_patterns(["he", "she", "\"q\\"].into_iter());
+        let json: serde_json::Value =
+            serde_json::from_str(&AutomationDump::create(&automation).to_json()).unwrap();
+
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), automation.num_nodes());
+        assert_eq!(nodes[0]["index"], 0);
+        assert_eq!(json["pattern_lens"], serde_json::json!([2, 3, 3]));
+        assert_eq!(json["patterns"], serde_json::json!(["he", "she", "\"q\\"]));
+    }
+
+    #[cfg(feature = "mermaid")]
+    #[test]
+    fn mermaid_edges() {
+        let automation = Automation::build(["she", "he", "hers"].into_iter());
+        let mermaid = AutomationDump::create(&automation).to_mermaid();
+
+        assert!(mermaid.starts_with("graph TD\n"));
+        assert_eq!(mermaid.matches(" --> ").count(), 7);
+        assert_eq!(mermaid.matches(" -.-> ").count(), 8);
+        assert!(mermaid.contains("N0 --> N1"));
+    }
+
+    #[test]
+    fn hex_labels() {
+        let automation = Automation::build([&b"\x00\xff"[..], b"\xff"].into_iter());
+        let dump = automation.dump_with(|b| format!("{:02x}", b), |output| format!("#{}", output));
+        let node = |prefix: &'static [u8]| automation.node_for_prefix(&prefix).unwrap();
+
+        assert_eq!(dump.nodes[node(b"\x00")].node, "00");
+        assert_eq!(dump.nodes[node(b"\x00\xff")].node, "ff");
+        assert_eq!(dump.nodes[node(b"\x00\xff")].outputs, [0, 1]);
+        assert_eq!(dump.output_labels, ["#0", "#1"]);
+        assert!(dump.patterns.is_none());
+    }
+}
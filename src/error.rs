@@ -0,0 +1,24 @@
+use core::{error::Error, fmt};
+
+/// Error returned when an automation cannot be built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// No patterns were given.
+    NoPatterns,
+    /// A pattern of `len` chars exceeded the length limit.
+    PatternTooLong { len: usize },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::NoPatterns => write!(f, "no patterns were given"),
+            BuildError::PatternTooLong { len } => {
+                write!(f, "pattern of {} chars exceeds the length limit", len)
+            }
+        }
+    }
+}
+
+impl Error for BuildError {}
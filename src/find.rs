@@ -0,0 +1,930 @@
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+use core::{cmp::Reverse, ops::ControlFlow};
+
+use crate::{Automation, HashBuilder, Pattern};
+
+/// A match found in a haystack, as byte offsets into it.
+///
+/// `&haystack[start..end]` is always a valid slice holding the matched text.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Match {
+    pub pattern_id: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl<P: Pattern<Char = char>, V, S: HashBuilder> Automation<P, V, S> {
+    /// Yields `(pattern_id, end)` for every overlapping match in `haystack`,
+    /// where `end` is the byte offset just past the last char of the match,
+    /// so `&haystack[..end]` always ends with the matched pattern.
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut search = self.search();
+        let start = self.start_outputs().iter().map(|&output| (output, 0));
+
+        start.chain(haystack.char_indices().flat_map(move |(i, c)| {
+            let end = i + c.len_utf8();
+            search.step(&c).iter().map(move |&output| (output, end))
+        }))
+    }
+
+    /// Same as [`Automation::matches`], but yields `(pattern_id, start, end)` in chars
+    /// instead of bytes, so `haystack.chars().skip(start).take(end - start)` is the match.
+    pub fn find_iter_char_indexed<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        let mut search = self.search();
+        let start = self.start_outputs().iter().map(|&output| (output, 0, 0));
+
+        start.chain(haystack.chars().enumerate().flat_map(move |(i, c)| {
+            let end = i + 1;
+            search
+                .step(&c)
+                .iter()
+                .map(move |&output| (output, end - self.pattern_lens[output], end))
+        }))
+    }
+
+    /// Lazy counterpart of [`Automation::run`]: yields `(position, pattern_id)` as `chars`
+    /// is consumed, so it also works on endless inputs.
+    ///
+    /// Like with [`Automation::run`], the empty pattern is not reported before the first char.
+    pub fn search_chars<'a, I>(&'a self, chars: I) -> impl Iterator<Item = (usize, usize)> + 'a
+    where
+        I: Iterator<Item = char> + 'a,
+    {
+        let mut search = self.search();
+
+        chars.enumerate().flat_map(move |(position, c)| {
+            search
+                .step(&c)
+                .iter()
+                .map(move |&output| (position, output))
+        })
+    }
+
+    /// Same as [`Automation::find_iter`], but yields the original pattern
+    /// instead of its output ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the automation was not built with [`Automation::build_with_patterns`].
+    pub fn find_iter_patterns<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = (&'a P, usize)> + 'a {
+        self.find_iter(haystack)
+            .map(move |(output, end)| (self.pattern(output), end))
+    }
+
+    /// Same as [`Automation::find_iter`], but yields full [`Match`] spans.
+    pub fn matches<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Match> + 'a {
+        self.find_iter(haystack)
+            .map(move |(pattern_id, end)| Match {
+                pattern_id,
+                start: match_start(haystack, end, self.pattern_lens[pattern_id]),
+                end,
+            })
+    }
+
+    /// Same as [`Automation::matches`], but yields a single match for every span,
+    /// the one with the lowest output ID, if several patterns match the exact same text.
+    ///
+    /// This can only happen with patterns matching more than one text,
+    /// e.g. the ones of [`Automation::build_classes`]. Identical patterns
+    /// are never reported twice in the first place, see [`Automation::duplicate_of`].
+    pub fn matches_dedup_spans<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = Match> + 'a {
+        let mut search = self.search();
+        let start = self.start_outputs().iter().map(|&pattern_id| Match {
+            pattern_id,
+            start: 0,
+            end: 0,
+        });
+
+        start.chain(haystack.char_indices().flat_map(move |(i, c)| {
+            let end = i + c.len_utf8();
+            let outputs = search.step(&c);
+
+            // Matches ending at the same char span the same text if they have the same length.
+            outputs
+                .iter()
+                .copied()
+                .filter(move |&output| {
+                    let len = self.pattern_lens[output];
+                    !outputs
+                        .iter()
+                        .any(|&other| self.pattern_lens[other] == len && other < output)
+                })
+                .map(move |pattern_id| Match {
+                    pattern_id,
+                    start: match_start(haystack, end, self.pattern_lens[pattern_id]),
+                    end,
+                })
+        }))
+    }
+
+    /// Same as [`Automation::matches`], but yields `(pattern_id, snippet)`, where `snippet`
+    /// is the matched text with up to `window` chars of context on either side.
+    pub fn snippets<'a>(
+        &'a self,
+        haystack: &'a str,
+        window: usize,
+    ) -> impl Iterator<Item = (usize, String)> + 'a {
+        self.matches(haystack).map(move |m| {
+            let start = match_start(haystack, m.start, window);
+            let end = haystack[m.end..]
+                .char_indices()
+                .nth(window)
+                .map_or(haystack.len(), |(i, _)| m.end + i);
+
+            (m.pattern_id, String::from(&haystack[start..end]))
+        })
+    }
+
+    /// Yields `(pattern_id, start, end)` byte spans of non-overlapping matches.
+    ///
+    /// Matches are reported as soon as they end, after which the search restarts
+    /// from the root, so the next match can only begin after the reported one.
+    /// If several patterns end at the same position, the longest one
+    /// (i.e. the leftmost starting one) wins, ties are broken by lowest output ID.
+    ///
+    /// An empty pattern takes part like any other one, so it is reported at every
+    /// position where nothing else ends, which hides matches spanning that position.
+    /// Use [`Automation::find_iter_leftmost_longest`] to only get empty matches
+    /// where no longer match starts.
+    pub fn find_iter_nonoverlapping<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        let mut search = self.search();
+        let start = self
+            .longest_output(self.start_outputs())
+            .map(|output| (output, 0, 0));
+
+        start
+            .into_iter()
+            .chain(haystack.char_indices().filter_map(move |(i, c)| {
+                let output = self.longest_output(search.step(&c))?;
+                search.reset();
+
+                let end = i + c.len_utf8();
+                let start = match_start(haystack, end, self.pattern_lens[output]);
+                Some((output, start, end))
+            }))
+    }
+
+    /// Yields `(pattern_id, start, end)` byte spans of non-overlapping matches
+    /// with leftmost-longest semantics.
+    ///
+    /// Of all matches starting leftmost, the longest one is reported,
+    /// ties are broken by lowest output ID. The search then resumes right after it.
+    pub fn find_iter_leftmost_longest<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        let mut from = 0;
+        let mut first = true;
+
+        core::iter::from_fn(move || {
+            let (output, start, end) = self.leftmost_longest(haystack, from, first)?;
+            from = end;
+            first = false;
+            Some((output, start, end))
+        })
+    }
+
+    // Finds the leftmost-longest match starting at or after `from`. An empty match
+    // right at `from` is only considered for the first search, later ones start
+    // where the previous match ended.
+    fn leftmost_longest(
+        &self,
+        haystack: &str,
+        from: usize,
+        empty_at_from: bool,
+    ) -> Option<(usize, usize, usize)> {
+        let mut search = self.search();
+
+        // Best match so far as `(start, end)` in chars after `from`,
+        // followed by its byte end and output.
+        let mut best: Option<(usize, usize, usize, usize)> = self
+            .start_outputs()
+            .first()
+            .filter(|_| empty_at_from)
+            .map(|&output| (0, 0, from, output));
+
+        for (n, (i, c)) in haystack[from..].char_indices().enumerate() {
+            let outputs = search.step(&c);
+            let end = n + 1;
+
+            // Matches ending later cannot start before the trie path of the current node,
+            // so once the path has moved past the best match, nothing can beat it anymore.
+            let path_start = end - self.nodes[search.current].depth;
+            if best.is_some_and(|(start, ..)| path_start > start) {
+                break;
+            }
+
+            for &output in outputs {
+                let start = end - self.pattern_lens[output];
+                let better = best.is_none_or(|(best_start, best_end, _, best_output)| {
+                    (start, Reverse(end), output) < (best_start, Reverse(best_end), best_output)
+                });
+
+                if better {
+                    best = Some((start, end, from + i + c.len_utf8(), output));
+                }
+            }
+        }
+
+        best.map(|(_, _, end, output)| {
+            let start = match_start(haystack, end, self.pattern_lens[output]);
+            (output, start, end)
+        })
+    }
+
+    /// Calls `f(pattern_id, end)` for every overlapping match in `haystack`,
+    /// with the same arguments [`Automation::find_iter`] yields.
+    pub fn for_each_match<F: FnMut(usize, usize)>(&self, haystack: &str, mut f: F) {
+        let mut search = self.search();
+
+        for &output in self.start_outputs() {
+            f(output, 0);
+        }
+
+        for (i, c) in haystack.char_indices() {
+            for &output in search.step(&c) {
+                f(output, i + c.len_utf8());
+            }
+        }
+    }
+
+    /// Same as [`Automation::for_each_match`], but stops scanning as soon as `f` breaks.
+    pub fn try_for_each_match<F>(&self, haystack: &str, mut f: F) -> ControlFlow<()>
+    where
+        F: FnMut(usize, usize) -> ControlFlow<()>,
+    {
+        let mut search = self.search();
+
+        for &output in self.start_outputs() {
+            f(output, 0)?;
+        }
+
+        for (i, c) in haystack.char_indices() {
+            for &output in search.step(&c) {
+                f(output, i + c.len_utf8())?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Returns `true` if any pattern occurs in `haystack`, stopping at the first match.
+    pub fn is_match(&self, haystack: &str) -> bool {
+        let mut search = self.search();
+        !self.start_outputs().is_empty() || haystack.chars().any(|c| !search.step(&c).is_empty())
+    }
+
+    /// Counts all overlapping matches in `haystack`.
+    pub fn count_matches(&self, haystack: &str) -> usize {
+        let mut search = self.search();
+        let start = self.start_outputs().len();
+        start
+            + haystack
+                .chars()
+                .map(|c| search.step(&c).len())
+                .sum::<usize>()
+    }
+
+    /// Counts the overlapping matches in `haystack` per pattern, indexed by output ID.
+    ///
+    /// Duplicate patterns are never reported, so their count stays `0`.
+    pub fn match_histogram(&self, haystack: &str) -> Vec<usize> {
+        let mut counts = vec![0; self.output_cnt];
+        let mut search = self.search();
+
+        for &output in self.start_outputs() {
+            counts[output] += 1;
+        }
+
+        for c in haystack.chars() {
+            for &output in search.step(&c) {
+                counts[output] += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Returns the output ID of the longest pattern `haystack` ends with, if any,
+    /// ties are broken by lowest output ID.
+    pub fn ends_with_any(&self, haystack: &str) -> Option<usize> {
+        let mut search = self.search();
+        let outputs = haystack
+            .chars()
+            .fold(self.start_outputs(), |_, c| search.step(&c));
+        self.longest_output(outputs)
+    }
+
+    /// Returns the output ID of the shortest pattern `haystack` starts with, if any.
+    ///
+    /// Only the trie path along the start of `haystack` is walked, no failure links
+    /// are followed, and the walk stops at the first pattern found.
+    pub fn starts_with_any(&self, haystack: &str) -> Option<usize> {
+        let mut node_idx = 0;
+        let mut chars = haystack.chars();
+
+        loop {
+            if let Some(&output) = self.nodes[node_idx].direct_outputs.first() {
+                return Some(output);
+            }
+
+            let c = chars.next()?;
+            let folded = P::fold(&c).unwrap_or(c);
+            node_idx = self.nodes[node_idx].enter_child(&folded)?;
+        }
+    }
+
+    /// Maps the ID of every pattern occurring in `haystack` to the end offset
+    /// of its first occurrence, as [`Automation::find_iter`] yields it.
+    ///
+    /// The map is ordered by pattern ID.
+    pub fn first_occurrences(&self, haystack: &str) -> BTreeMap<usize, usize> {
+        let mut first = BTreeMap::new();
+
+        for (output, end) in self.find_iter(haystack) {
+            first.entry(output).or_insert(end);
+        }
+
+        first
+    }
+}
+
+impl<P: Pattern<Char = char>, S: HashBuilder> Automation<P, u32, S> {
+    /// Same as [`Automation::find_iter_nonoverlapping`], but treats the values attached
+    /// with [`Automation::build_with_values`] as priorities: if several patterns end
+    /// at the same position, the one with the highest priority wins,
+    /// ties are broken by the longest pattern, then by lowest output ID.
+    pub fn find_iter_by_priority<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        let mut search = self.search();
+        let start = self
+            .highest_priority(self.start_outputs())
+            .map(|output| (output, 0, 0));
+
+        start
+            .into_iter()
+            .chain(haystack.char_indices().filter_map(move |(i, c)| {
+                let output = self.highest_priority(search.step(&c))?;
+                search.reset();
+
+                let end = i + c.len_utf8();
+                let start = match_start(haystack, end, self.pattern_lens[output]);
+                Some((output, start, end))
+            }))
+    }
+
+    fn highest_priority(&self, outputs: &[usize]) -> Option<usize> {
+        outputs.iter().copied().max_by_key(|&output| {
+            (
+                self.values[output],
+                self.pattern_lens[output],
+                Reverse(output),
+            )
+        })
+    }
+}
+
+impl<P: Pattern<Char = char>, S: HashBuilder> Automation<P, bool, S> {
+    /// Same as [`Automation::find_iter`], but treats the values attached with
+    /// [`Automation::build_with_values`] as negative flags, and leaves out the matches
+    /// of the patterns flagged `true`.
+    ///
+    /// Negative patterns only filter their own matches, matches of other patterns
+    /// overlapping them are still reported.
+    pub fn find_iter_filtered<'a>(
+        &'a self,
+        haystack: &'a str,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.find_iter(haystack)
+            .filter(move |&(output, _)| !self.values[output])
+    }
+}
+
+impl<P: Pattern<Char = u8>, V, S: HashBuilder> Automation<P, V, S> {
+    /// Byte counterpart of [`Automation::find_iter`]: yields `(pattern_id, end)` for every
+    /// overlapping match in `haystack`, where `end` is the offset just past the last byte
+    /// of the match.
+    pub fn find_iter_bytes<'a>(
+        &'a self,
+        haystack: &'a [u8],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut search = self.search();
+        let start = self.start_outputs().iter().map(|&output| (output, 0));
+
+        start.chain(
+            haystack
+                .iter()
+                .enumerate()
+                .flat_map(move |(i, b)| search.step(b).iter().map(move |&output| (output, i + 1))),
+        )
+    }
+
+    /// Yields the [`Match`] spans of all overlapping matches in `haystack`
+    /// that are valid UTF-8, skipping the ones that start or end inside a multibyte char.
+    pub fn matches_valid_utf8<'a>(
+        &'a self,
+        haystack: &'a [u8],
+    ) -> impl Iterator<Item = Match> + 'a {
+        self.find_iter_bytes(haystack)
+            .filter_map(move |(pattern_id, end)| {
+                let start = end - self.pattern_lens[pattern_id];
+                core::str::from_utf8(&haystack[start..end]).ok()?;
+
+                Some(Match {
+                    pattern_id,
+                    start,
+                    end,
+                })
+            })
+    }
+
+    /// Byte counterpart of [`Automation::is_match`].
+    pub fn is_match_bytes(&self, haystack: &[u8]) -> bool {
+        let mut search = self.search();
+        !self.start_outputs().is_empty() || haystack.iter().any(|b| !search.step(b).is_empty())
+    }
+
+    /// Byte counterpart of [`Automation::count_matches`].
+    pub fn count_matches_bytes(&self, haystack: &[u8]) -> usize {
+        let mut search = self.search();
+        let start = self.start_outputs().len();
+        start + haystack.iter().map(|b| search.step(b).len()).sum::<usize>()
+    }
+
+    /// Yields `(pattern_id, end)` for every overlapping match in `haystack`,
+    /// where `end` is the offset just past the last byte of the match.
+    ///
+    /// While the search is at the root, bytes that no pattern starts with are skipped
+    /// without stepping the automation, which is much faster on haystacks
+    /// with long stretches that cannot match.
+    pub fn find_iter_prefiltered<'a>(
+        &'a self,
+        haystack: &'a [u8],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let starts = self.start_bytes();
+        let mut search = self.search();
+        let mut outputs = self.start_outputs();
+        let mut pos = 0;
+
+        core::iter::from_fn(move || loop {
+            if let Some((&output, rest)) = outputs.split_first() {
+                outputs = rest;
+                return Some((output, pos));
+            }
+
+            if search.current == 0 {
+                // Nothing can match before the next start byte, so jump right to it.
+                pos += haystack[pos..].iter().position(|&b| starts[b as usize])?;
+            }
+
+            outputs = search.step(haystack.get(pos)?);
+            pos += 1;
+        })
+    }
+
+    // Returns which input bytes lead out of the root.
+    fn start_bytes(&self) -> [bool; 256] {
+        let root = &self.nodes[0];
+
+        // An empty pattern matches at every byte, so none can be skipped.
+        let mut starts = [!self.start_outputs().is_empty(); 256];
+
+        for b in 0..=u8::MAX {
+            let folded = P::fold(&b).unwrap_or(b);
+            starts[b as usize] |= root.contains(&folded);
+        }
+
+        starts
+    }
+}
+
+impl<P: Pattern, V, S: HashBuilder> Automation<P, V, S> {
+    /// Feeds all of `input` and collects `(position, pattern_id)` of every match,
+    /// where `position` is the zero-based index in `input` of the last char of the match.
+    ///
+    /// An empty match before the first char has no such position, so the empty pattern
+    /// is only reported after every char, see [`Automation::start_outputs`].
+    pub fn run<I: IntoIterator<Item = P::Char>>(&self, input: I) -> Vec<(usize, usize)> {
+        let mut search = self.search();
+        let mut matches = Vec::new();
+
+        for (position, c) in input.into_iter().enumerate() {
+            matches.extend(search.step(&c).iter().map(|&output| (position, output)));
+        }
+
+        matches
+    }
+
+    /// Yields `(position, pattern_id)` for every match in `iter`, which yields
+    /// `(position, item)` pairs. Each item is converted by `map` into the char fed to the
+    /// automation, or skipped if `map` returns `None`, while `position` is reported as is.
+    ///
+    /// This allows to normalize the input, e.g. to drop the `'\r'` of CRLF line breaks,
+    /// while still reporting matches at their offsets in the original input.
+    ///
+    /// Like with [`Automation::run`], the empty pattern is not reported before the first char.
+    pub fn search_mapped<'a, I, T, F>(
+        &'a self,
+        iter: I,
+        mut map: F,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a
+    where
+        I: Iterator<Item = (usize, T)> + 'a,
+        F: FnMut(T) -> Option<P::Char> + 'a,
+    {
+        let mut search = self.search();
+
+        iter.filter_map(move |(position, item)| Some((position, map(item)?)))
+            .flat_map(move |(position, c)| {
+                search
+                    .step(&c)
+                    .iter()
+                    .map(move |&output| (position, output))
+            })
+    }
+
+    pub(crate) fn longest_output(&self, outputs: &[usize]) -> Option<usize> {
+        // Of all patterns ending at the same position, the longest starts leftmost.
+        outputs
+            .iter()
+            .copied()
+            .max_by_key(|&output| (self.pattern_lens[output], Reverse(output)))
+    }
+}
+
+// Returns the byte offset at which a match of `len` chars ending at `end` starts.
+fn match_start(haystack: &str, end: usize, len: usize) -> usize {
+    match len.checked_sub(1) {
+        Some(n) => haystack[..end]
+            .char_indices()
+            .rev()
+            .nth(n)
+            .map_or(0, |(i, _)| i),
+        None => end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leftmost_longest_prefers_longest() {
+        let automation = Automation::build(["a", "ab", "abc"].into_iter());
+        let matches: Vec<_> = automation.find_iter_leftmost_longest("abcd").collect();
+
+        assert_eq!(matches, [(2, 0, 3)]);
+    }
+
+    #[test]
+    fn first_occurrences_of_repeated_keyword() {
+        let automation = Automation::build(["he", "she", "xyz"].into_iter());
+        let first = automation.first_occurrences("he said she and he left");
+
+        assert_eq!(first, BTreeMap::from([(0, 2), (1, 11)]));
+    }
+
+    #[test]
+    fn empty_pattern_matches_at_every_position() {
+        let automation = Automation::build(["he", "", "she"].into_iter());
+        let matches: Vec<_> = automation.find_iter("she").collect();
+
+        assert_eq!(matches, [(1, 0), (1, 1), (1, 2), (2, 3), (0, 3), (1, 3)]);
+        assert_eq!(automation.count_matches("she"), 6);
+        assert_eq!(
+            automation
+                .find_iter_leftmost_longest("she")
+                .collect::<Vec<_>>(),
+            [(2, 0, 3)]
+        );
+
+        assert_eq!(automation.find_iter("").collect::<Vec<_>>(), [(1, 0)]);
+        assert!(automation.is_match(""));
+        assert_eq!(automation.count_matches(""), 1);
+        assert_eq!(automation.ends_with_any(""), Some(1));
+
+        let bytes = Automation::build([&b""[..], b"ab"].into_iter());
+        assert_eq!(
+            bytes.find_iter_bytes(b"ab").collect::<Vec<_>>(),
+            [(0, 0), (0, 1), (1, 2), (0, 2)]
+        );
+        assert_eq!(bytes.count_matches_bytes(b""), 1);
+    }
+
+    #[test]
+    fn is_match_at_last_char() {
+        let automation = Automation::build(["xyz", "d"].into_iter());
+
+        assert!(automation.is_match("abcd"));
+        assert!(!automation.is_match("abc"));
+
+        let bytes = Automation::build([&b"xyz"[..], b"d"].into_iter());
+        assert!(bytes.is_match_bytes(b"abcd"));
+        assert!(!bytes.is_match_bytes(b"abc"));
+    }
+
+    #[test]
+    fn count_overlapping_matches() {
+        let automation = Automation::build(["a", "aa"].into_iter());
+        assert_eq!(automation.count_matches("aaa"), 5);
+
+        let bytes = Automation::build([&b"a"[..], b"aa"].into_iter());
+        assert_eq!(bytes.count_matches_bytes(b"aaa"), 5);
+        assert_eq!(bytes.count_matches_bytes(b"a\xffa"), 2);
+    }
+
+    #[test]
+    fn nonoverlapping_prefers_longest() {
+        let automation = Automation::build(["e", "he", "she"].into_iter());
+        let matches: Vec<_> = automation.find_iter_nonoverlapping("he she").collect();
+
+        assert_eq!(matches, [(1, 0, 2), (2, 3, 6)]);
+    }
+
+    #[test]
+    fn run_custom_iterator() {
+        // Yields the decimal digits of a number, least significant first.
+        struct Digits(u32);
+
+        impl Iterator for Digits {
+            type Item = char;
+
+            fn next(&mut self) -> Option<char> {
+                if self.0 == 0 {
+                    return None;
+                }
+
+                let digit = char::from_digit(self.0 % 10, 10);
+                self.0 /= 10;
+                digit
+            }
+        }
+
+        let automation = Automation::build(["12", "21"].into_iter());
+
+        assert_eq!(automation.run(Digits(1213)), [(2, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn matches_slice_multibyte_chars() {
+        let patterns = ["🦀rs", "s", "é🦀"];
+        let automation = Automation::build(patterns.into_iter());
+        let haystack = "aé🦀rs!";
+
+        let matches: Vec<_> = automation.matches(haystack).collect();
+        let texts: Vec<_> = matches.iter().map(|m| &haystack[m.start..m.end]).collect();
+
+        assert_eq!(texts, ["é🦀", "🦀rs", "s"]);
+        for (m, text) in matches.iter().zip(texts) {
+            assert_eq!(patterns[m.pattern_id], text);
+        }
+    }
+
+    #[test]
+    fn prefiltered_matches_unfiltered() {
+        let mut haystack = vec![b'.'; 1000];
+        haystack.extend_from_slice(b"ushers");
+        haystack.extend(vec![b'-'; 1000]);
+        haystack.extend_from_slice(b"HERS");
+
+        let plain = Automation::build([&b"he"[..], b"she", b"hers"].into_iter());
+        let expected: Vec<_> = plain.find_iter_bytes(&haystack).collect();
+        assert_eq!(expected.len(), 3);
+        assert_eq!(
+            plain.find_iter_prefiltered(&haystack).collect::<Vec<_>>(),
+            expected
+        );
+
+        let folded = Automation::build_ascii_case_insensitive([&b"hErs"[..]].into_iter());
+        let expected: Vec<_> = folded.find_iter_bytes(&haystack).collect();
+        assert_eq!(expected, [(0, 1006), (0, 2010)]);
+        assert_eq!(
+            folded.find_iter_prefiltered(&haystack).collect::<Vec<_>>(),
+            expected
+        );
+
+        let empty = Automation::build([&b""[..], b"rs"].into_iter());
+        let expected: Vec<_> = empty.find_iter_bytes(&haystack).collect();
+        assert_eq!(expected.len(), haystack.len() + 1 + 1);
+        assert_eq!(
+            empty.find_iter_prefiltered(&haystack).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn search_chars_on_endless_input() {
+        let automation = Automation::build(["ca"].into_iter());
+        let matches: Vec<_> = automation
+            .search_chars("abc".chars().cycle())
+            .take(3)
+            .collect();
+
+        assert_eq!(matches, [(3, 0), (6, 0), (9, 0)]);
+    }
+
+    #[test]
+    fn overlapping_byte_matches() {
+        let automation = Automation::build([&b"ab"[..], b"b", b"abab"].into_iter());
+        let matches: Vec<_> = automation.find_iter_bytes(b"ababab").collect();
+
+        assert_eq!(
+            matches,
+            [
+                (0, 2),
+                (1, 2),
+                (2, 4),
+                (0, 4),
+                (1, 4),
+                (2, 6),
+                (0, 6),
+                (1, 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn for_each_match_like_find_iter() {
+        let automation = Automation::build(["he", "she", "", "hers"].into_iter());
+        let haystack = "ushers said hé";
+
+        let mut matches = Vec::new();
+        automation.for_each_match(haystack, |output, end| matches.push((output, end)));
+
+        assert_eq!(matches, automation.find_iter(haystack).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn try_for_each_match_stops_early() {
+        let automation = Automation::build(["he", "she"].into_iter());
+        let mut seen = Vec::new();
+
+        let flow = automation.try_for_each_match("he she he", |output, end| {
+            seen.push((output, end));
+            if seen.len() == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert_eq!(seen, [(0, 2), (1, 6)]);
+        assert_eq!(
+            automation.try_for_each_match("he", |_, _| ControlFlow::Continue(())),
+            ControlFlow::Continue(())
+        );
+    }
+
+    #[test]
+    fn char_indexed_after_multibyte_char() {
+        let automation = Automation::build(["he", "é"].into_iter());
+        let haystack = "éhe";
+        let matches: Vec<_> = automation.find_iter_char_indexed(haystack).collect();
+
+        assert_eq!(matches, [(1, 0, 1), (0, 1, 3)]);
+        let (_, start, end) = matches[1];
+        assert_eq!(
+            haystack
+                .chars()
+                .skip(start)
+                .take(end - start)
+                .collect::<String>(),
+            "he"
+        );
+    }
+
+    #[test]
+    fn priority_beats_length() {
+        let automation = Automation::build_with_values([("she", 1u32), ("he", 5)].into_iter());
+        let matches: Vec<_> = automation.find_iter_by_priority("she").collect();
+        assert_eq!(matches, [(1, 1, 3)]);
+
+        let tied = Automation::build_with_values([("she", 1u32), ("he", 1)].into_iter());
+        assert_eq!(
+            tied.find_iter_by_priority("she").collect::<Vec<_>>(),
+            [(0, 0, 3)]
+        );
+    }
+
+    #[test]
+    fn search_mapped_drops_carriage_returns() {
+        let automation = Automation::build(["a\nb"].into_iter());
+        let haystack = "a\r\nb a\nb";
+
+        let matches: Vec<_> = automation
+            .search_mapped(haystack.char_indices(), |c| (c != '\r').then_some(c))
+            .collect();
+
+        // Positions are byte offsets of the last char in the original haystack.
+        assert_eq!(matches, [(3, 0), (7, 0)]);
+    }
+
+    #[test]
+    fn filtered_skips_negative_patterns() {
+        let automation = Automation::build_with_values(
+            [("he", false), ("she", true), ("hers", false)].into_iter(),
+        );
+        let matches: Vec<_> = automation.find_iter_filtered("ushers").collect();
+
+        // `"she"` is left out, but `"he"` inside it is still reported.
+        assert_eq!(matches, [(0, 4), (2, 6)]);
+    }
+
+    #[test]
+    fn ends_with_longest_pattern() {
+        let automation = Automation::build(["he", "xyzhe", "yzhe"].into_iter());
+
+        assert_eq!(automation.ends_with_any("xyzhe"), Some(1));
+        assert_eq!(automation.ends_with_any("ahe"), Some(0));
+        assert_eq!(automation.ends_with_any("hex"), None);
+    }
+
+    #[test]
+    fn starts_with_shortest_pattern() {
+        let automation = Automation::build(["hers", "he", "she"].into_iter());
+
+        assert_eq!(automation.starts_with_any("herself"), Some(1));
+        assert_eq!(automation.starts_with_any("hers and his"), Some(1));
+        assert_eq!(automation.starts_with_any("ushers"), None);
+        assert_eq!(automation.starts_with_any("h"), None);
+    }
+
+    #[test]
+    fn find_iter_yields_patterns() {
+        let automation = Automation::build_with_patterns(["he", "she", "hers"].into_iter());
+        let matches: Vec<_> = automation.find_iter_patterns("ushers").collect();
+
+        assert_eq!(matches, [(&"she", 4), (&"he", 4), (&"hers", 6)]);
+    }
+
+    #[test]
+    fn histogram_per_pattern() {
+        let automation = Automation::build(["he", "she", "hers", "he", "xyz"].into_iter());
+
+        assert_eq!(automation.match_histogram("she he hers"), [3, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn snippets_clamp_to_haystack() {
+        let automation = Automation::build(["he"].into_iter());
+        let snippets = |haystack, window| {
+            automation
+                .snippets(haystack, window)
+                .map(|(_, snippet)| snippet)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(snippets("here", 5), ["here"]);
+        assert_eq!(snippets("ça hé he ok", 2), ["é he o"]);
+        assert_eq!(snippets("he😀😀x", 2), ["he😀😀"]);
+    }
+
+    #[test]
+    fn dedup_spans_of_class_and_plain_patterns() {
+        let pattern = crate::ClassPattern::parse("[ab]t").unwrap();
+        let mut automation = Automation::build_classes([pattern].into_iter());
+        automation.add_patterns([vec!['a', 't'], vec!['t']].into_iter());
+
+        let span = |pattern_id, start, end| Match {
+            pattern_id,
+            start,
+            end,
+        };
+
+        assert_eq!(automation.matches("at bt").count(), 5);
+        assert_eq!(
+            automation.matches_dedup_spans("at bt").collect::<Vec<_>>(),
+            [span(0, 0, 2), span(2, 1, 2), span(0, 3, 5), span(2, 4, 5)]
+        );
+    }
+
+    #[test]
+    fn valid_utf8_skips_split_chars() {
+        let automation = Automation::build([&b"\xa9x"[..], "é".as_bytes(), b"x"].into_iter());
+        let haystack = "éx".as_bytes();
+
+        let spans: Vec<_> = automation
+            .matches_valid_utf8(haystack)
+            .map(|m| (m.pattern_id, m.start, m.end))
+            .collect();
+
+        // `"\xa9x"` starts inside `'é'`, so it is left out.
+        assert_eq!(automation.find_iter_bytes(haystack).count(), 3);
+        assert_eq!(spans, [(1, 0, 2), (2, 2, 3)]);
+    }
+}
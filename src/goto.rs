@@ -0,0 +1,167 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::{mem, slice};
+
+use crate::map::{self, HashBuilder, Key, LargeMap};
+
+// Nodes with at most this many children keep them in a vector,
+// larger ones are promoted to a hash map.
+const SMALL_GOTO_LIMIT: usize = 8;
+
+/// Goto transitions of a node.
+///
+/// Most trie nodes only have a few children, for which a vector scanned linearly
+/// is both smaller and about as fast as a hash map.
+pub(crate) enum Goto<C, S> {
+    Small(Vec<(C, usize)>),
+    // Boxed so that the small variant, which almost every node uses, stays small.
+    #[allow(clippy::box_collection)]
+    Large(Box<LargeMap<C, S>>),
+}
+
+impl<C: Key, S: HashBuilder> Goto<C, S> {
+    pub fn new() -> Self {
+        Goto::Small(Vec::new())
+    }
+
+    pub fn get(&self, c: &C) -> Option<&usize> {
+        match self {
+            Goto::Small(children) => children
+                .iter()
+                .find(|(x, _)| x == c)
+                .map(|(_, node_idx)| node_idx),
+            Goto::Large(children) => children.get(c),
+        }
+    }
+
+    pub fn contains_key(&self, c: &C) -> bool {
+        self.get(c).is_some()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        match self {
+            Goto::Small(children) => children.shrink_to_fit(),
+            Goto::Large(children) => children.shrink_to_fit(),
+        }
+    }
+
+    pub fn insert(&mut self, c: C, node_idx: usize, hasher: &S) {
+        match self {
+            Goto::Small(children) => match children.iter().position(|(x, _)| *x == c) {
+                Some(i) => children[i].1 = node_idx,
+                None if children.len() == SMALL_GOTO_LIMIT => {
+                    let mut large = LargeMap::with_hasher(hasher.clone());
+                    for (c, node_idx) in children.drain(..) {
+                        large.insert(c, node_idx);
+                    }
+                    large.insert(c, node_idx);
+                    *self = Goto::Large(Box::new(large));
+                }
+                None => children.push((c, node_idx)),
+            },
+            Goto::Large(children) => {
+                children.insert(c, node_idx);
+            }
+        }
+    }
+}
+
+impl<C, S> Goto<C, S> {
+    pub fn iter(&self) -> Iter<'_, C> {
+        match self {
+            Goto::Small(children) => Iter::Small(children.iter()),
+            Goto::Large(children) => Iter::Large(children.iter()),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &C> {
+        self.iter().map(|(c, _)| c)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &usize> {
+        self.iter().map(|(_, node_idx)| node_idx)
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        let entry = mem::size_of::<(C, usize)>();
+
+        match self {
+            Goto::Small(children) => children.capacity() * entry,
+            Goto::Large(children) => {
+                mem::size_of::<LargeMap<C, S>>() + map::large_heap_bytes(children)
+            }
+        }
+    }
+}
+
+pub(crate) enum Iter<'a, C> {
+    Small(slice::Iter<'a, (C, usize)>),
+    Large(map::Iter<'a, C, usize>),
+}
+
+impl<'a, C> Iterator for Iter<'a, C> {
+    type Item = (&'a C, &'a usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Small(iter) => iter.next().map(|(c, node_idx)| (c, node_idx)),
+            Iter::Large(iter) => iter.next(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::vec::Vec;
+    use core::mem;
+
+    use crate::{
+        map::{self, Map},
+        Automation,
+    };
+
+    #[test]
+    fn small_gotos_match_and_shrink() {
+        let mut automation = Automation::build(["he", "she", "his", "hers"].into_iter());
+        let matches: Vec<_> = automation.find_iter("ushers his").collect();
+        assert_eq!(matches, [(1, 4), (0, 4), (3, 6), (2, 10)]);
+
+        // The same transitions, if every node kept them in a hash map.
+        automation.shrink_to_fit();
+        let goto_bytes: usize = automation
+            .nodes
+            .iter()
+            .map(|node| mem::size_of_val(&node.goto) + node.goto.heap_bytes())
+            .sum();
+        let hash_map_bytes: usize = automation
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut map: Map<char, usize> =
+                    node.goto.iter().map(|(&c, &next)| (c, next)).collect();
+                map.shrink_to_fit();
+                mem::size_of_val(&map) + map::heap_bytes(&map)
+            })
+            .sum();
+        assert!(
+            goto_bytes < hash_map_bytes,
+            "{} {}",
+            goto_bytes,
+            hash_map_bytes
+        );
+    }
+
+    // Chars only need to be `Eq + Hash` with `std`.
+    #[test]
+    fn unordered_chars() {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        struct Symbol(u8);
+
+        let patterns = [[Symbol(1), Symbol(2)], [Symbol(2), Symbol(3)]];
+        let automation = Automation::build(patterns.iter().map(|pattern| &pattern[..]));
+        let mut search = automation.search();
+
+        assert!(search.next(&Symbol(1)).is_empty());
+        assert_eq!(search.next(&Symbol(2)), [0]);
+        assert_eq!(search.next(&Symbol(3)), [1]);
+    }
+}
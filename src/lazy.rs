@@ -0,0 +1,135 @@
+use alloc::vec::Vec;
+
+use crate::{Automation, AutomationSearch, Pattern};
+
+/// Automation that does not merge the outputs of failure link targets into the nodes,
+/// so every node only holds the outputs of the patterns ending exactly at it.
+///
+/// The eager merge of [`Automation::build`] copies the outputs of every suffix into each
+/// node, which takes up to `nodes * patterns` output IDs for pattern sets with many shared
+/// suffixes, whereas this keeps a single copy per pattern. In exchange, collecting the
+/// outputs at a position takes one hop per failure link up to the root instead of none.
+///
+/// Searches report the same outputs, in the same order, as those of an eager automation
+/// built from the same patterns.
+pub struct LazyAutomation<P: Pattern> {
+    automation: Automation<P>,
+}
+
+impl<P: Pattern> LazyAutomation<P> {
+    /// Builds the automation from the given patterns,
+    /// assigning output IDs in the order the patterns come in.
+    pub fn build(items: impl Iterator<Item = P>) -> Self {
+        let mut automation = Automation::new();
+
+        automation.add_items(items);
+        automation.link_failures(false);
+
+        Self { automation }
+    }
+
+    /// Returns the number of patterns the automation was built from.
+    pub fn num_patterns(&self) -> usize {
+        self.automation.num_patterns()
+    }
+
+    /// Returns the length of the pattern with output ID `id`, in `Char`s.
+    pub fn pattern_len(&self, id: usize) -> usize {
+        self.automation.pattern_len(id)
+    }
+
+    /// Estimates the heap memory used by the automation, in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        self.automation.heap_bytes()
+    }
+
+    pub fn search(&self) -> LazySearch<'_, P> {
+        LazySearch::new(self)
+    }
+}
+
+impl<P: Pattern<Char = char>> LazyAutomation<P> {
+    /// Yields `(pattern_id, end)` for every overlapping match in `haystack`,
+    /// where `end` is the byte offset just past the last char of the match.
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut search = self.automation.search();
+        let start = self
+            .automation
+            .start_outputs()
+            .iter()
+            .map(|&output| (output, 0));
+
+        start.chain(haystack.char_indices().flat_map(move |(i, c)| {
+            let end = i + c.len_utf8();
+            search.next(&c);
+            search.iter_outputs().map(move |output| (output, end))
+        }))
+    }
+}
+
+pub struct LazySearch<'a, P: Pattern> {
+    search: AutomationSearch<'a, P>,
+    outputs: Vec<usize>,
+}
+
+impl<'a, P: Pattern> LazySearch<'a, P> {
+    pub fn new(automation: &'a LazyAutomation<P>) -> Self {
+        Self {
+            search: automation.automation.search(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Feeds the next char and returns the outputs of all patterns ending at it,
+    /// longer patterns first.
+    pub fn next(&mut self, c: &P::Char) -> &[usize] {
+        self.search.next(c);
+
+        self.outputs.clear();
+        self.outputs.extend(self.search.iter_outputs());
+
+        &self.outputs
+    }
+
+    /// Lazily yields the outputs of all patterns ending at the last fed char,
+    /// without collecting them like [`LazySearch::next`] does.
+    pub fn iter_outputs(&self) -> impl Iterator<Item = usize> + 'a {
+        self.search.iter_outputs()
+    }
+
+    /// Returns the search to its initial state, as if no chars were fed yet.
+    pub fn reset(&mut self) {
+        self.search.reset();
+        self.outputs.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lazy_matches_eager() {
+        let pattern_sets: [&[&str]; 4] = [
+            &["he", "she", "his", "hers"],
+            &["aaaa", "aaa", "aa", "a"],
+            &["abc", "bc", "c", "bcd", "abc"],
+            &["", "x", "xy"],
+        ];
+        let haystack = "ushers said his share of hershey aaaaa abcd xyz";
+
+        for patterns in pattern_sets {
+            let eager = Automation::build(patterns.iter());
+            let lazy = LazyAutomation::build(patterns.iter());
+            let expected: Vec<_> = eager.find_iter(haystack).collect();
+
+            assert_eq!(lazy.find_iter(haystack).collect::<Vec<_>>(), expected);
+
+            let mut search = lazy.search();
+            let mut eager_search = eager.search();
+            for c in haystack.chars() {
+                assert_eq!(search.next(&c), eager_search.next(&c));
+            }
+        }
+    }
+}
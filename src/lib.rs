@@ -1,16 +1,69 @@
-use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    hash::Hash,
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use alloc::{
+    collections::VecDeque,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
 };
+use core::{fmt, hash::Hash};
 
+use goto::Goto;
+use map::{Key, Map, Set};
+
+mod case;
+mod class;
+mod dfa;
 mod dump;
+mod error;
+mod find;
+mod goto;
+mod lazy;
+mod map;
+mod overlap;
+mod raw;
+mod replace;
+mod reverse;
+mod stats;
+#[cfg(feature = "std")]
+mod stream;
+mod wildcard;
 
+pub use case::{AsciiCaseInsensitive, CaseFold, UnicodeCaseInsensitive};
+pub use class::ClassPattern;
+pub use dfa::{Dfa, DfaSearch};
 pub use dump::AutomationDump;
+pub use error::BuildError;
+pub use find::Match;
+pub use lazy::{LazyAutomation, LazySearch};
+pub use map::{DefaultHashBuilder, DefaultHasher, HashBuilder};
+pub use raw::RawNode;
+pub use reverse::Reversed;
+pub use stats::TransitionStats;
+#[cfg(feature = "std")]
+pub use stream::StreamSearcher;
+pub use wildcard::{WildcardAutomation, WildcardSearch};
 
 pub trait Pattern {
+    /// The `Eq` and `Hash` impls must agree with each other, otherwise searches may miss
+    /// matches. Without `std`, goto maps are ordered, so `Char` must also be `Ord`,
+    /// agreeing with `Eq` as well.
+    #[cfg(feature = "std")]
     type Char: Eq + Hash;
+    #[cfg(not(feature = "std"))]
+    type Char: Eq + Hash + Ord;
 
     fn iter(&self) -> impl Iterator<Item = Self::Char>;
+
+    /// Maps a char of the searched input before it is fed to the automation,
+    /// e.g. to fold its case. Returns `None` to keep the char as is.
+    fn fold(_c: &Self::Char) -> Option<Self::Char> {
+        None
+    }
 }
 
 impl Pattern for &str {
@@ -21,26 +74,61 @@ impl Pattern for &str {
     }
 }
 
+impl Pattern for String {
+    type Char = char;
+
+    fn iter(&self) -> impl Iterator<Item = Self::Char> {
+        self.chars()
+    }
+}
+
+/// Slices work over any alphabet, e.g. bytes or `u32` symbol IDs.
+impl<C: Key + Copy> Pattern for &[C] {
+    type Char = C;
+
+    fn iter(&self) -> impl Iterator<Item = Self::Char> {
+        <[C]>::iter(self).copied()
+    }
+}
+
+/// With `Pattern` in scope, `vec.iter()` resolves to this impl, which yields the elements
+/// by value, instead of the slice iterator. Use `vec.as_slice().iter()` for the latter.
+impl<C: Key + Clone> Pattern for Vec<C> {
+    type Char = C;
+
+    fn iter(&self) -> impl Iterator<Item = Self::Char> {
+        <[C]>::iter(self).cloned()
+    }
+}
+
 impl<P: Pattern> Pattern for &P {
     type Char = P::Char;
 
     fn iter(&self) -> impl Iterator<Item = Self::Char> {
         (*self).iter()
     }
+
+    fn fold(c: &Self::Char) -> Option<Self::Char> {
+        P::fold(c)
+    }
 }
 
-struct AutomationNode<C> {
-    goto: HashMap<C, usize>,
+struct AutomationNode<C, S> {
+    goto: Goto<C, S>,
     failure: usize,
     outputs: Vec<usize>,
+    direct_outputs: Vec<usize>,
+    depth: usize,
 }
 
-impl<C: Eq + Hash> AutomationNode<C> {
-    pub fn new() -> Self {
+impl<C: Key, S: HashBuilder> AutomationNode<C, S> {
+    pub fn new(depth: usize) -> Self {
         Self {
-            goto: HashMap::new(),
+            goto: Goto::new(),
             failure: 0,
             outputs: Vec::new(),
+            direct_outputs: Vec::new(),
+            depth,
         }
     }
 
@@ -49,32 +137,213 @@ impl<C: Eq + Hash> AutomationNode<C> {
     }
 
     fn enter_child(&self, c: &C) -> Option<usize> {
-        self.goto.get(c).map(|&x| x)
+        self.goto.get(c).copied()
     }
 
-    fn add_child(&mut self, c: C, node_idx: usize) {
-        self.goto.insert(c, node_idx);
+    fn add_child(&mut self, c: C, node_idx: usize, hasher: &S) {
+        self.goto.insert(c, node_idx, hasher);
     }
 
     fn add_output(&mut self, output: usize) {
         self.outputs.push(output);
+        self.direct_outputs.push(output);
     }
 }
 
-pub struct Automation<P: Pattern> {
-    nodes: Vec<AutomationNode<P::Char>>,
+/// With the `serde` feature, automatons can be serialized. Only the trie is stored,
+/// everything derived from it is computed again, after validating it, while deserializing.
+pub struct Automation<P: Pattern, V = (), S = DefaultHashBuilder> {
+    nodes: Vec<AutomationNode<P::Char, S>>,
     output_cnt: usize,
+    pattern_lens: Vec<usize>,
+    patterns: Option<Vec<P>>,
+    duplicates: Map<usize, usize>,
+    // Outputs of class patterns ending at several nodes, in increasing order. Since they
+    // are not the only pattern of any of these nodes, plain patterns never duplicate them.
+    class_outputs: Vec<usize>,
+    values: Vec<V>,
+    sorted_outputs: bool,
+    max_outputs: usize,
+    hasher: S,
 }
 
 impl<P: Pattern> Automation<P> {
+    /// Builds the automation from the given patterns,
+    /// assigning output IDs in the order the patterns come in.
+    ///
+    /// Identical patterns still get their own output IDs, but only the first of them
+    /// is ever reported by searches, see [`Automation::duplicate_of`].
+    ///
+    /// An empty pattern matches at every position, from before the first char
+    /// to after the last one, so `n + 1` times in a haystack of `n` chars.
+    /// Every haystack search reports all of these matches. [`AutomationSearch::next`]
+    /// reports it after every char, the match before the first one is left to
+    /// [`Automation::start_outputs`].
     pub fn build(items: impl Iterator<Item = P>) -> Self {
-        let mut automation = Automation {
-            nodes: Vec::new(),
-            output_cnt: 0,
+        let mut automation = Self::new();
+
+        automation.add_items(items);
+        automation.build_failure();
+
+        automation
+    }
+
+    /// Same as [`Automation::build`], but reserves room for `node_capacity` nodes upfront,
+    /// which avoids reallocating the nodes while inserting large pattern sets.
+    ///
+    /// A trie has at most one node per pattern char plus the root, so the total length
+    /// of the patterns is a good hint.
+    pub fn build_with_capacity(items: impl Iterator<Item = P>, node_capacity: usize) -> Self {
+        let mut automation = Self::new();
+        automation.nodes.reserve(node_capacity);
+
+        automation.add_items(items);
+        automation.build_failure();
+
+        automation
+    }
+
+    /// Same as [`Automation::build`], but sorts the patterns before inserting them,
+    /// so the output IDs only depend on the set of patterns, not on their order.
+    ///
+    /// The output ID of a pattern is its index in the sorted pattern list.
+    pub fn build_sorted(items: impl Iterator<Item = P>) -> Self
+    where
+        P: Ord,
+    {
+        let mut items: Vec<P> = items.collect();
+        items.sort();
+
+        Self::build(items.into_iter())
+    }
+
+    /// Same as [`Automation::build`], but fails if there are no patterns,
+    /// instead of building an automation that never matches.
+    pub fn try_build(items: impl Iterator<Item = P>) -> Result<Self, BuildError> {
+        let automation = Self::build(items);
+
+        if automation.output_cnt == 0 {
+            return Err(BuildError::NoPatterns);
+        }
+
+        Ok(automation)
+    }
+
+    /// Same as [`Automation::build`], but fails on the first pattern
+    /// longer than `max_len` chars, without building the rest of the trie.
+    pub fn build_with_limits(
+        items: impl Iterator<Item = P>,
+        max_len: usize,
+    ) -> Result<Self, BuildError> {
+        let mut automation = Self::new();
+
+        for item in items {
+            automation.insert_limited(item, (), max_len)?;
+        }
+        automation.build_failure();
+
+        Ok(automation)
+    }
+
+    /// Same as [`Automation::build`], but reports progress for large pattern sets.
+    ///
+    /// `on_progress` is called with the number of patterns inserted so far after every
+    /// 1024 patterns. Once all patterns are inserted, it is called with their total number
+    /// right before computing the failure links and again once they are done,
+    /// since that takes a while for large pattern sets as well.
+    pub fn build_with_progress(
+        items: impl Iterator<Item = P>,
+        mut on_progress: impl FnMut(usize),
+    ) -> Self {
+        const PROGRESS_INTERVAL: usize = 1024;
+
+        let mut automation = Self::new();
+
+        for item in items {
+            let inserted = automation.add_item(item) + 1;
+
+            if inserted % PROGRESS_INTERVAL == 0 {
+                on_progress(inserted);
+            }
+        }
+
+        on_progress(automation.output_cnt);
+        automation.build_failure();
+        on_progress(automation.output_cnt);
+
+        automation
+    }
+
+    /// Same as [`Automation::build`], but also keeps the original patterns
+    /// so they can be looked up by output ID with [`Automation::pattern`].
+    pub fn build_with_patterns(items: impl Iterator<Item = P>) -> Self {
+        let mut automation = Self::new();
+        automation.patterns = Some(Vec::new());
+
+        automation.add_items(items);
+        automation.build_failure();
+
+        automation
+    }
+
+    /// Builds an automation with the patterns of both `a` and `b`, keeping the output IDs
+    /// of `a` and offsetting those of `b` by [`Automation::num_patterns`] of `a`.
+    ///
+    /// The trie is rebuilt from the original patterns, which are retained in the result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either automation was not built with [`Automation::build_with_patterns`].
+    pub fn merge(a: Self, b: Self) -> Self {
+        let expect_patterns = |automation: Self| {
+            automation
+                .patterns
+                .expect("patterns are not retained, use `build_with_patterns`")
         };
+        let (a, b) = (expect_patterns(a), expect_patterns(b));
 
-        // Add root node
-        automation.nodes.push(AutomationNode::new());
+        Self::build_with_patterns(a.into_iter().chain(b))
+    }
+
+    /// Builds an automation with only the patterns for which `predicate(id, &pattern)`
+    /// returns `true`, which get new output IDs in the same order as before,
+    /// without gaps.
+    ///
+    /// The trie is rebuilt from the original patterns, which are retained in the result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the automation was not built with [`Automation::build_with_patterns`].
+    pub fn retain_patterns(&self, predicate: impl Fn(usize, &P) -> bool) -> Self
+    where
+        P: Clone,
+    {
+        let patterns = self
+            .patterns
+            .as_ref()
+            .expect("patterns are not retained, use `build_with_patterns`");
+
+        Self::build_with_patterns(
+            patterns
+                .iter()
+                .enumerate()
+                .filter(|(id, pattern)| predicate(*id, pattern))
+                .map(|(_, pattern)| pattern.clone()),
+        )
+    }
+
+    /// Creates an automation without patterns, to be filled with [`Automation::add_item`]
+    /// and completed with [`Automation::finalize`].
+    pub fn new() -> Self {
+        Self::empty(Default::default())
+    }
+}
+
+impl<P: Pattern, S: HashBuilder> Automation<P, (), S> {
+    /// Same as [`Automation::build`], but uses `hasher` for the goto maps
+    /// of nodes with many children, which are hash maps with `std`.
+    pub fn build_with_hasher(items: impl Iterator<Item = P>, hasher: S) -> Self {
+        let mut automation = Self::empty(hasher);
 
         automation.add_items(items);
         automation.build_failure();
@@ -82,123 +351,1463 @@ impl<P: Pattern> Automation<P> {
         automation
     }
 
+    /// Inserts more patterns into an already built automation, assigning them
+    /// the next output IDs.
+    ///
+    /// The failure links are rebuilt from scratch for the whole automation,
+    /// so the cost is proportional to its total size, not to the added patterns.
+    pub fn add_patterns(&mut self, items: impl Iterator<Item = P>) {
+        self.add_items(items);
+        self.build_failure();
+    }
+
     fn add_items(&mut self, items: impl Iterator<Item = P>) {
         for item in items {
             self.add_item(item);
         }
     }
 
-    fn add_item(&mut self, item: P) {
+    /// Inserts a pattern into the trie and returns its output ID.
+    ///
+    /// The failure links are not updated, so searches may miss matches
+    /// until [`Automation::finalize`] is called.
+    pub fn add_item(&mut self, item: P) -> usize {
+        self.insert(item, ())
+    }
+}
+
+impl<P: Pattern, V> Automation<P, V> {
+    /// Same as [`Automation::build`], but attaches a value to every pattern,
+    /// which can be looked up by output ID with [`Automation::value`].
+    pub fn build_with_values(items: impl Iterator<Item = (P, V)>) -> Self {
+        let mut automation = Self::empty(Default::default());
+
+        for (item, value) in items {
+            automation.insert(item, value);
+        }
+        automation.build_failure();
+
+        automation
+    }
+}
+
+impl<P: Pattern, V, S: HashBuilder> Automation<P, V, S> {
+    fn empty(hasher: S) -> Self {
+        let mut automation = Automation {
+            nodes: Vec::new(),
+            output_cnt: 0,
+            pattern_lens: Vec::new(),
+            patterns: None,
+            duplicates: Map::new(),
+            class_outputs: Vec::new(),
+            values: Vec::new(),
+            sorted_outputs: false,
+            max_outputs: usize::MAX,
+            hasher,
+        };
+
+        // Add root node
+        automation.nodes.push(AutomationNode::new(0));
+
+        automation
+    }
+
+    fn insert(&mut self, item: P, value: V) -> usize {
+        match self.insert_limited(item, value, usize::MAX) {
+            Ok(output_idx) => output_idx,
+            Err(_) => unreachable!("no pattern is longer than usize::MAX"),
+        }
+    }
+
+    // Fails without adding an output if the pattern is longer than `max_len`,
+    // in which case the trie keeps the nodes of its first `max_len` chars.
+    fn insert_limited(&mut self, item: P, value: V, max_len: usize) -> Result<usize, BuildError> {
         let mut node_idx = 0;
+        let mut len = 0;
 
         for c in item.iter() {
+            len += 1;
+
+            if len > max_len {
+                let len = item.iter().count();
+                return Err(BuildError::PatternTooLong { len });
+            }
+
             if let Some(n) = self.nodes[node_idx].enter_child(&c) {
                 node_idx = n;
             } else {
                 let new_node_idx = self.nodes.len();
-                self.nodes.push(AutomationNode::new());
-                self.nodes[node_idx].add_child(c, new_node_idx);
+                self.nodes.push(AutomationNode::new(len));
+                self.nodes[node_idx].add_child(c, new_node_idx, &self.hasher);
                 node_idx = new_node_idx;
             }
         }
 
         let output_idx = self.output_cnt;
-        self.nodes[node_idx].add_output(output_idx);
+
+        // An exact same pattern was already inserted if the node has a direct output,
+        // other than one of a class pattern that also ends elsewhere.
+        let class_outputs = &self.class_outputs;
+        let original = self.nodes[node_idx]
+            .direct_outputs
+            .as_slice()
+            .iter()
+            .find(|output| class_outputs.binary_search(output).is_err());
+
+        match original {
+            Some(&original) => {
+                self.duplicates.insert(output_idx, original);
+            }
+            None => self.nodes[node_idx].add_output(output_idx),
+        }
+
+        self.pattern_lens.push(len);
+        self.values.push(value);
         self.output_cnt += 1;
+
+        if let Some(patterns) = &mut self.patterns {
+            patterns.push(item);
+        }
+
+        Ok(output_idx)
+    }
+
+    /// Removes all patterns, keeping the allocations for reuse.
+    ///
+    /// Output IDs start at `0` again for patterns added afterwards.
+    /// Retained patterns stay retained, the other options are kept as well.
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0] = AutomationNode::new(0);
+        self.output_cnt = 0;
+        self.pattern_lens.clear();
+        self.duplicates.clear();
+        self.class_outputs.clear();
+        self.values.clear();
+
+        if let Some(patterns) = &mut self.patterns {
+            patterns.clear();
+        }
+    }
+
+    /// Computes the failure links after patterns were inserted with [`Automation::add_item`].
+    ///
+    /// Calling it multiple times is fine, the links are rebuilt from scratch every time.
+    pub fn finalize(&mut self) {
+        self.build_failure();
+    }
+
+    /// Returns the value attached to the pattern with output ID `id`.
+    ///
+    /// Duplicate patterns keep their own values, even though searches
+    /// only report the output ID of the first one.
+    pub fn value(&self, id: usize) -> &V {
+        &self.values[id]
+    }
+
+    /// Sorts the outputs reported by searches at every position by pattern length,
+    /// then by output ID, so they come in a deterministic order.
+    ///
+    /// This is off by default, in which case the order is unspecified. It stays
+    /// in effect when patterns are added later.
+    pub fn sort_outputs(&mut self) {
+        self.sorted_outputs = true;
+        self.sort_node_outputs();
+    }
+
+    /// Caps the number of outputs reported by searches at every position to `max`,
+    /// keeping those of the shortest patterns, ties are broken by lowest output ID.
+    ///
+    /// This rebuilds the failure links. It stays in effect when patterns are added later.
+    pub fn truncate_outputs(&mut self, max: usize) {
+        self.max_outputs = max;
+        self.build_failure();
+    }
+
+    /// Returns the length of the pattern with output ID `id`, in `Char`s.
+    pub fn pattern_len(&self, id: usize) -> usize {
+        self.pattern_lens[id]
+    }
+
+    /// Returns the original pattern with output ID `id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the automation was not built with [`Automation::build_with_patterns`].
+    pub fn pattern(&self, id: usize) -> &P {
+        let patterns = self
+            .patterns
+            .as_ref()
+            .expect("patterns are not retained, use `build_with_patterns`");
+        &patterns[id]
+    }
+
+    /// Returns the output ID of the first identical pattern, if the pattern with
+    /// output ID `id` is a duplicate. Searches only report that first output ID.
+    pub fn duplicate_of(&self, id: usize) -> Option<usize> {
+        self.duplicates.get(&id).copied()
+    }
+
+    /// Returns the node reached from `node` by the goto transition on `c`, if there is one.
+    ///
+    /// Nodes are identified by their index, the root being `0`.
+    pub fn goto(&self, node: usize, c: &P::Char) -> Option<usize> {
+        self.nodes[node].enter_child(c)
+    }
+
+    /// Returns `true` if `pattern` is exactly one of the patterns of the automation.
+    ///
+    /// Only walks the trie along `pattern`, no search is run.
+    pub fn is_pattern(&self, pattern: &P) -> bool {
+        self.node_for_prefix(pattern)
+            .is_some_and(|node_idx| !self.nodes[node_idx].direct_outputs.is_empty())
+    }
+
+    /// Returns the node reached from the root by following the goto transitions
+    /// along `prefix`, or `None` if no pattern starts with `prefix`.
+    ///
+    /// Failure links are never taken, so the trie path of the returned node is exactly
+    /// `prefix`. Its continuations can then be listed through [`Automation::goto`].
+    pub fn node_for_prefix(&self, prefix: &P) -> Option<usize> {
+        prefix
+            .iter()
+            .try_fold(0, |node_idx, c| self.nodes[node_idx].enter_child(&c))
+    }
+
+    /// Yields `(pattern_id, node)` for every pattern and the node its trie path ends at,
+    /// ordered by node. Duplicate patterns are left out, see [`Automation::duplicate_of`].
+    pub fn terminals(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.nodes.iter().enumerate().flat_map(|(node_idx, node)| {
+            node.direct_outputs
+                .as_slice()
+                .iter()
+                .map(move |&output| (output, node_idx))
+        })
+    }
+
+    /// Reconstructs the patterns from the trie, indexed by output ID,
+    /// so this works even if the original patterns are not retained.
+    ///
+    /// The patterns are returned as the chars they were inserted as,
+    /// e.g. folded to lowercase for case-insensitive patterns.
+    ///
+    /// A pattern ending at several nodes, like the ones of [`Automation::build_classes`],
+    /// has no single trie path, so `None` is returned for it.
+    pub fn patterns(&self) -> Vec<Option<Vec<P::Char>>>
+    where
+        P::Char: Clone,
+    {
+        let mut patterns = vec![None; self.output_cnt];
+        let mut path = Vec::new();
+        let mut stack: Vec<(usize, Option<&P::Char>)> = vec![(0, None)];
+
+        // The trie path of a node is the path of its parent followed by its own char,
+        // and the parent is always the last node visited at the next lower depth.
+        while let Some((node_idx, c)) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            path.truncate(node.depth.saturating_sub(1));
+            path.extend(c.cloned());
+
+            for &output in &node.direct_outputs {
+                if self.class_outputs.binary_search(&output).is_err() {
+                    patterns[output] = Some(path.clone());
+                }
+            }
+
+            stack.extend(node.goto.iter().map(|(c, &next)| (next, Some(c))));
+        }
+
+        for (&duplicate, &original) in &self.duplicates {
+            patterns[duplicate] = patterns[original].clone();
+        }
+
+        patterns
+    }
+
+    /// Returns the distinct chars with a goto transition from the root,
+    /// i.e. the first chars of all non-empty patterns, in no particular order.
+    pub fn root_alphabet(&self) -> impl Iterator<Item = &P::Char> {
+        self.nodes[0].goto.keys()
+    }
+
+    /// Returns the failure link of `node`, i.e. the node of its longest proper suffix
+    /// present in the trie. The root links to itself.
+    pub fn failure(&self, node: usize) -> usize {
+        self.nodes[node].failure
+    }
+
+    /// Returns the outputs of `node`, including the ones merged through failure links.
+    pub fn outputs(&self, node: usize) -> &[usize] {
+        &self.nodes[node].outputs
     }
 
-    fn get_node(&self, idx: usize) -> &AutomationNode<P::Char> {
+    fn get_node(&self, idx: usize) -> &AutomationNode<P::Char, S> {
         &self.nodes[idx]
     }
 
     fn build_failure(&mut self) {
+        self.link_failures(true);
+    }
+
+    // With `merge_outputs` unset, every node only keeps its direct outputs,
+    // which is all that lazy searches read.
+    fn link_failures(&mut self, merge_outputs: bool) {
         // Initializes failre function F[i] = lps(i) for each node i that is not root,
         // where lps(i) is the longest proper suffix of node i that is inside the trie.
 
+        // Drop the outputs merged by a previous build.
+        for node in &mut self.nodes {
+            node.outputs.clone_from(&node.direct_outputs);
+        }
+        self.truncate_node_outputs(0);
+
         // Use BFS to traverse the nodes of the trie in the order of increasing length.
         let mut queue = VecDeque::new();
         queue.push_back(0);
 
         while let Some(node_index) = queue.pop_front() {
-            for (c, &next_node_index) in self.nodes[node_index].goto.iter() {
-                let lps = if node_index != 0 {
-                    let mut lps = node_index;
+            // Compute the failure links of all children first,
+            // since the goto map of the node is borrowed while iterating it.
+            let links: Vec<(usize, usize)> = self.nodes[node_index]
+                .goto
+                .iter()
+                .map(|(c, &next_node_index)| (next_node_index, self.find_failure(node_index, c)))
+                .collect();
 
-                    // Find longest proper suffix for the next node.
-                    loop {
-                        lps = self.nodes[lps].failure;
+            for (next_node_index, lps) in links {
+                // The suffix is shorter than the node, so this only breaks if the `Eq` and `Hash`
+                // (or `Ord`) impls of `Char` disagree. Fall back to the root in that case,
+                // a failure link to the node itself would make searches loop forever.
+                debug_assert_ne!(next_node_index, lps);
+                let lps = if lps == next_node_index { 0 } else { lps };
 
-                        if lps == 0 || self.nodes[lps].goto.contains_key(c) {
-                            break;
-                        }
-                    }
+                self.nodes[next_node_index].failure = lps;
+                queue.push_back(next_node_index);
+
+                if !merge_outputs {
+                    self.nodes[next_node_index].outputs = Vec::new();
+                    continue;
+                }
 
-                    self.nodes[lps].goto.get(c).map(|&x| x).unwrap_or(0)
-                } else {
-                    // There are no proper suffixes for all nodes
-                    // directly accessible from root (the nodes of length 1).
-                    // Set failure to the root (0).
-                    0
-                };
-
-                assert_ne!(next_node_index, lps);
-                unsafe {
-                    // SAFETY: safe because `next_node_index` != `node_index`.
-                    let next_node = &mut *(self.nodes.as_ptr().add(next_node_index) as *const _
-                        as *mut AutomationNode<P::Char>);
-                    next_node.failure = lps;
-
-                    // Merge outputs with lps
-                    let h: HashSet<usize> =
-                        HashSet::from_iter(next_node.outputs.iter().map(|&x| x));
-
-                    for &output in &self.nodes[lps].outputs {
-                        if !h.contains(&output) {
-                            next_node.outputs.push(output);
-                        }
+                // Merge outputs with lps
+                let h: Set<usize> = Set::from_iter(self.nodes[next_node_index].outputs.clone());
+                let mut merged = Vec::new();
+
+                for &output in &self.nodes[lps].outputs {
+                    if !h.contains(&output) {
+                        merged.push(output);
                     }
                 }
 
-                queue.push_back(next_node_index);
+                self.nodes[next_node_index].outputs.extend(merged);
+
+                // The outputs of `lps` are already truncated, which does not lose any
+                // of the shortest ones, since they are all shorter than the direct ones.
+                self.truncate_node_outputs(next_node_index);
+            }
+        }
+
+        if self.sorted_outputs {
+            self.sort_node_outputs();
+        }
+    }
+
+    fn truncate_node_outputs(&mut self, node_idx: usize) {
+        let pattern_lens = &self.pattern_lens;
+        let outputs = &mut self.nodes[node_idx].outputs;
+
+        if outputs.len() > self.max_outputs {
+            outputs.sort_unstable_by_key(|&output| (pattern_lens[output], output));
+            outputs.truncate(self.max_outputs);
+        }
+    }
+
+    fn sort_node_outputs(&mut self) {
+        let pattern_lens = &self.pattern_lens;
+
+        for node in &mut self.nodes {
+            node.outputs
+                .sort_unstable_by_key(|&output| (pattern_lens[output], output));
+        }
+    }
+
+    fn find_failure(&self, node_index: usize, c: &P::Char) -> usize {
+        if node_index == 0 {
+            // There are no proper suffixes for all nodes
+            // directly accessible from root (the nodes of length 1).
+            // Set failure to the root (0).
+            return 0;
+        }
+
+        let mut lps = node_index;
+
+        // Find longest proper suffix for the next node.
+        loop {
+            lps = self.nodes[lps].failure;
+
+            if lps == 0 || self.nodes[lps].goto.contains_key(c) {
+                break;
             }
         }
+
+        self.nodes[lps].goto.get(c).copied().unwrap_or(0)
     }
 
     pub fn dump(&self) -> AutomationDump
     where
-        P: ToString,
         P::Char: ToString,
     {
         AutomationDump::create(self)
     }
 
-    pub fn search(&self) -> AutomationSearch<P> {
+    /// Same as [`Automation::dump`], but renders chars with `node_fmt`
+    /// and output IDs with `output_fmt`, e.g. to dump byte automatons as hex.
+    pub fn dump_with<FN, FC>(&self, node_fmt: FC, output_fmt: FN) -> AutomationDump
+    where
+        FC: FnMut(&P::Char) -> String,
+        FN: FnMut(usize) -> String,
+    {
+        AutomationDump::create_with(self, node_fmt, output_fmt)
+    }
+
+    pub fn search(&self) -> AutomationSearch<'_, P, V, S> {
         AutomationSearch::new(self)
     }
+
+    /// Returns the outputs of the patterns matching before any char is fed,
+    /// i.e. the output ID of the empty pattern, if there is one.
+    pub fn start_outputs(&self) -> &[usize] {
+        &self.nodes[0].outputs
+    }
 }
 
-pub struct AutomationSearch<'a, P: Pattern> {
-    automation: &'a Automation<P>,
+impl<P: Pattern> Default for Automation<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Prints every node with its goto transitions, failure link and outputs,
+// without needing the `ToString` bounds of `dump`.
+impl<P: Pattern, V, S> fmt::Debug for Automation<P, V, S>
+where
+    P::Char: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Automation {{")?;
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            write!(f, "    {}: goto {{", idx)?;
+
+            for (i, (c, next)) in node.goto.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{:?} -> {}", c, next)?;
+            }
+
+            writeln!(
+                f,
+                "}}, failure {}, outputs {:?}",
+                node.failure, node.outputs
+            )?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+// The automation is plain owned data, so it can be shared between threads,
+// each running its own search over it. Fails to compile if that ever changes.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Automation<&str>>();
+    assert_send_sync::<Automation<&[u8]>>();
+    assert_send_sync::<AutomationSearch<'_, &str>>();
+    assert_send_sync::<Dfa>();
+};
+
+// State of a search that can never match again.
+const DEAD: usize = usize::MAX;
+
+pub struct AutomationSearch<'a, P: Pattern, V = (), S = DefaultHashBuilder> {
+    automation: &'a Automation<P, V, S>,
     current: usize,
+    pos: usize,
+    last_outputs: usize,
+    overlapping: bool,
+    // Set once a non-overlapping match was reported, the search restarts from the root
+    // on the next char, so that until then the state still describes the match.
+    restart: bool,
+}
+
+// Implemented manually, since deriving would require `P`, `V` and `S` to be `Clone`.
+impl<P: Pattern, V, S> Clone for AutomationSearch<'_, P, V, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
 
-impl<'a, P: Pattern> AutomationSearch<'a, P> {
-    pub fn new(automation: &'a Automation<P>) -> Self {
+impl<P: Pattern, V, S> Copy for AutomationSearch<'_, P, V, S> {}
+
+impl<'a, P: Pattern, V, S: HashBuilder> AutomationSearch<'a, P, V, S> {
+    pub fn new(automation: &'a Automation<P, V, S>) -> Self {
         Self {
             automation,
             current: 0,
+            pos: 0,
+            last_outputs: 0,
+            overlapping: true,
+            restart: false,
         }
     }
 
+    /// Switches between overlapping searches, the default, and non-overlapping ones.
+    ///
+    /// When not overlapping, only the longest pattern ending at a char is reported
+    /// (ties are broken by lowest output ID), after which the search restarts from the root
+    /// with the next char, so the next match can only begin after it. Until then, the state
+    /// still describes the match, e.g. for [`AutomationSearch::current_depth`]. This is how
+    /// [`Automation::find_iter_nonoverlapping`] picks its matches.
+    pub fn set_overlapping(&mut self, overlapping: bool) {
+        self.overlapping = overlapping;
+    }
+
+    /// Feeds the next char and returns the outputs of all patterns ending at it.
+    ///
+    /// The state carries over between calls, so feeding several inputs one
+    /// after another behaves as if they were concatenated. Call
+    /// [`AutomationSearch::reset`] in between to search them independently.
     pub fn next(&mut self, c: &P::Char) -> &[usize] {
-        let mut node = self.automation.get_node(self.current);
+        self.step(c)
+    }
+
+    /// Same as [`AutomationSearch::next`], but also returns the zero-based position
+    /// of `c`, i.e. the number of chars fed before it.
+    pub fn next_positioned(&mut self, c: &P::Char) -> (usize, &[usize]) {
+        let position = self.pos;
+        (position, self.step(c))
+    }
+
+    /// Returns the number of chars fed since the search was created or last reset.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns how many outputs the last fed char reported,
+    /// i.e. the length of the slice the last [`AutomationSearch::next`] returned.
+    pub fn last_output_count(&self) -> usize {
+        self.last_outputs
+    }
+
+    /// Feeds all of `chars`, calling `on_match(offset, pattern_id)` for every match,
+    /// where `offset` is the index in `chars` of the last char of the match.
+    ///
+    /// Like with [`AutomationSearch::next`], the state carries over, so matches
+    /// straddling consecutive slices are found as well.
+    pub fn feed_slice(&mut self, chars: &[P::Char], mut on_match: impl FnMut(usize, usize)) {
+        for (offset, c) in chars.iter().enumerate() {
+            for &output in self.step(c) {
+                on_match(offset, output);
+            }
+        }
+    }
+
+    /// Same as [`AutomationSearch::next`], but yields `(pattern_id, length)` for every output,
+    /// where `length` is the pattern length in `Char`s, so the start of each match
+    /// is at `length - 1` chars before the current one.
+    pub fn next_with_lengths(&mut self, c: &P::Char) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let automation = self.automation;
+
+        self.step(c)
+            .iter()
+            .map(move |&output| (output, automation.pattern_lens[output]))
+    }
+
+    /// Same as [`AutomationSearch::next`], but returns the matched patterns as strings.
+    ///
+    /// Unlike `next`, this allocates on every call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the automation was not built with [`Automation::build_with_patterns`].
+    pub fn next_strings(&mut self, c: &P::Char) -> Vec<String>
+    where
+        P: ToString,
+    {
+        let automation = self.automation;
+
+        self.step(c)
+            .iter()
+            .map(|&output| automation.pattern(output).to_string())
+            .collect()
+    }
+
+    /// Same as [`AutomationSearch::next`], but returns the values attached to the matched
+    /// patterns with [`Automation::build_with_values`], in the same order.
+    ///
+    /// Unlike `next`, this allocates on every call.
+    pub fn next_values(&mut self, c: &P::Char) -> Vec<V>
+    where
+        V: Clone,
+    {
+        let automation = self.automation;
+
+        self.step(c)
+            .iter()
+            .map(|&output| automation.value(output).clone())
+            .collect()
+    }
+
+    /// Returns the outputs of the patterns ending exactly at the current state,
+    /// excluding those reached through failure links.
+    ///
+    /// E.g. with patterns `"he"` and `"she"`, after feeding `"she"` [`AutomationSearch::next`]
+    /// reports both, while this only returns `"she"`.
+    pub fn direct_outputs(&self) -> &[usize] {
+        if self.current == DEAD {
+            return &[];
+        }
+
+        &self.automation.get_node(self.current).direct_outputs
+    }
+
+    /// Lazily yields the outputs of all patterns ending at the last fed char,
+    /// by walking the failure links from the current state up to the root.
+    ///
+    /// This is how outputs are collected by [`LazyAutomation`],
+    /// but it works the same for any automation.
+    /// Outputs of longer patterns come first.
+    pub fn iter_outputs(&self) -> impl Iterator<Item = usize> + 'a {
+        let automation = self.automation;
+        let start = (self.current != DEAD).then_some(self.current);
+
+        core::iter::successors(start, move |&node_idx| {
+            (node_idx != 0).then(|| automation.get_node(node_idx).failure)
+        })
+        .flat_map(move |node_idx| {
+            automation
+                .get_node(node_idx)
+                .direct_outputs
+                .as_slice()
+                .iter()
+                .copied()
+        })
+    }
+
+    /// Returns `true` if feeding `c` with [`AutomationSearch::next`] would report
+    /// any output, without feeding it.
+    pub fn would_match(&self, c: &P::Char) -> bool {
+        let mut lookahead = *self;
+        !lookahead.step(c).is_empty()
+    }
+
+    /// Returns `true` if the pattern with output ID `id` ends at the last fed char,
+    /// i.e. if `id` is among the outputs the last [`AutomationSearch::next`] returned.
+    pub fn contains_output(&self, id: usize) -> bool {
+        // A non-overlapping match only reports the longest of the outputs.
+        if self.restart {
+            return self.longest_output() == Some(id);
+        }
+
+        self.current != DEAD && self.automation.get_node(self.current).outputs.contains(&id)
+    }
+
+    /// Returns the output ID of the longest pattern ending at the last fed char,
+    /// ties are broken by lowest output ID.
+    pub fn longest_output(&self) -> Option<usize> {
+        if self.current == DEAD {
+            return None;
+        }
+
+        let outputs = &self.automation.get_node(self.current).outputs;
+        self.automation.longest_output(outputs)
+    }
+
+    /// Returns how many of the last fed chars form the longest pattern prefix
+    /// the search is currently in, i.e. the trie depth of the current state.
+    ///
+    /// After following failure links, this is the depth of the state landed on,
+    /// which can be much lower than before. Dead searches are at depth `0`.
+    pub fn current_depth(&self) -> usize {
+        if self.current == DEAD {
+            return 0;
+        }
+
+        self.automation.get_node(self.current).depth
+    }
+
+    /// Returns `true` if the search can never match again, which only happens once
+    /// [`AutomationSearch::next_anchored`] left the trie. Dead searches ignore every
+    /// fed char and report no outputs until [`AutomationSearch::reset`] is called.
+    pub fn is_dead(&self) -> bool {
+        self.current == DEAD
+    }
+
+    /// Returns the search to the root state, as if no chars were fed yet.
+    pub fn reset(&mut self) {
+        self.current = 0;
+        self.pos = 0;
+        self.last_outputs = 0;
+        self.restart = false;
+    }
+
+    /// Feeds the next char, matching only patterns that start at the first char.
+    ///
+    /// Failure links are never followed: once a char does not extend the current
+    /// trie path, the search is dead and reports no outputs until it is reset.
+    ///
+    /// An empty pattern only matches anchored before the first char,
+    /// so it is never reported here, see [`Automation::start_outputs`].
+    pub fn next_anchored(&mut self, c: &P::Char) -> &[usize] {
+        self.pos += 1;
+
+        let outputs = self.walk_anchored(c);
+        self.last_outputs = outputs.len();
+        outputs
+    }
+
+    fn step(&mut self, c: &P::Char) -> &'a [usize] {
+        self.pos += 1;
+
+        let outputs = self.walk(c);
+        self.last_outputs = outputs.len();
+        outputs
+    }
+
+    fn restart_if_matched(&mut self) {
+        if self.restart {
+            self.restart = false;
+            self.current = 0;
+        }
+    }
+
+    fn walk_anchored(&mut self, c: &P::Char) -> &'a [usize] {
+        self.restart_if_matched();
+
+        if self.current == DEAD {
+            return &[];
+        }
+
+        let folded = P::fold(c);
+        let c = folded.as_ref().unwrap_or(c);
+
+        match self.automation.get_node(self.current).enter_child(c) {
+            Some(next) => {
+                self.current = next;
+                &self.automation.get_node(next).direct_outputs
+            }
+            None => {
+                self.current = DEAD;
+                &[]
+            }
+        }
+    }
+
+    fn walk(&mut self, c: &P::Char) -> &'a [usize] {
+        self.restart_if_matched();
+
+        if self.current == DEAD {
+            return &[];
+        }
+
+        let folded = P::fold(c);
+        let c = folded.as_ref().unwrap_or(c);
+
+        let automation = self.automation;
+        let mut node = automation.get_node(self.current);
 
         while self.current != 0 && !node.contains(c) {
             self.current = node.failure;
-            node = self.automation.get_node(self.current);
+            node = automation.get_node(self.current);
         }
 
         self.current = node.enter_child(c).unwrap_or(0);
-        &self.automation.get_node(self.current).outputs
+        let outputs = &automation.get_node(self.current).outputs;
+
+        if self.overlapping {
+            return outputs;
+        }
+
+        let Some(longest) = automation.longest_output(outputs) else {
+            return &[];
+        };
+
+        self.restart = true;
+        outputs
+            .as_slice()
+            .iter()
+            .find(|&&output| output == longest)
+            .map_or(&[], core::slice::from_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_failure_suffix_chain() {
+        let automation = Automation::build(["aaaa", "aaa", "aa", "a"].into_iter());
+        let node = |prefix| automation.node_for_prefix(&prefix).unwrap();
+
+        assert_eq!(automation.nodes.len(), 5);
+        assert_eq!(automation.failure(node("a")), 0);
+        assert_eq!(automation.failure(node("aa")), node("a"));
+        assert_eq!(automation.failure(node("aaa")), node("aa"));
+        assert_eq!(automation.failure(node("aaaa")), node("aaa"));
+
+        assert_eq!(automation.outputs(node("a")), [3]);
+        assert_eq!(automation.outputs(node("aa")), [2, 3]);
+        assert_eq!(automation.outputs(node("aaa")), [1, 2, 3]);
+        assert_eq!(automation.outputs(node("aaaa")), [0, 1, 2, 3]);
+
+        let matches: Vec<_> = automation.find_iter("aaaaa").collect();
+        assert_eq!(matches.len(), 5 + 4 + 3 + 2);
+        assert!(matches.contains(&(0, 5)));
+        assert!(automation.verify_failure_links());
+    }
+
+    // Runs with `--no-default-features`, where the maps fall back to `BTreeMap`.
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn no_std_build_and_search() {
+        let automation = Automation::build(["he", "she", "hers"].into_iter());
+        let matches: Vec<_> = automation.find_iter_nonoverlapping("ushers").collect();
+
+        assert_eq!(matches, [(1, 1, 4)]);
+        assert_eq!(automation.run("ushers".chars()).len(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let automation = Automation::build(["he", "she", "hers"].into_iter());
+        let json = serde_json::to_string(&automation).unwrap();
+        let restored: Automation<&str> = serde_json::from_str(&json).unwrap();
+
+        let haystack = "ushers said she has hers";
+        let expected: Vec<_> = automation.find_iter(haystack).collect();
+        assert!(!expected.is_empty());
+        assert_eq!(restored.find_iter(haystack).collect::<Vec<_>>(), expected);
+        assert_eq!(restored.num_patterns(), automation.num_patterns());
+    }
+
+    #[test]
+    fn build_with_custom_hasher() {
+        // FNV-1a, seeded the same on every run.
+        #[derive(Clone, Default)]
+        struct Fnv;
+
+        struct FnvHasher(u64);
+
+        impl core::hash::Hasher for FnvHasher {
+            fn write(&mut self, bytes: &[u8]) {
+                for &b in bytes {
+                    self.0 = (self.0 ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3);
+                }
+            }
+
+            fn finish(&self) -> u64 {
+                self.0
+            }
+        }
+
+        impl core::hash::BuildHasher for Fnv {
+            type Hasher = FnvHasher;
+
+            fn build_hasher(&self) -> FnvHasher {
+                FnvHasher(0xcbf2_9ce4_8422_2325)
+            }
+        }
+
+        // More children of the root than fit in a small goto.
+        let patterns = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "ij", "jk"];
+        let automation = Automation::build_with_hasher(patterns.into_iter(), Fnv);
+        let expected = Automation::build(patterns.into_iter());
+        assert!(matches!(automation.nodes[0].goto, Goto::Large(_)));
+
+        let haystack = "the quick brown fox jumps over a lazy dog, hijk";
+        let matches: Vec<_> = automation.find_iter(haystack).collect();
+        assert!(matches.contains(&(9, 46)));
+        assert!(matches.contains(&(10, 47)));
+        assert_eq!(matches, expected.find_iter(haystack).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn byte_patterns() {
+        let automation = Automation::build([&b"\x00\x01"[..], b"\x01\x02"].into_iter());
+        let mut search = automation.search();
+        let outputs: Vec<Vec<usize>> = [0u8, 1, 2, 0, 1, 1]
+            .iter()
+            .map(|b| search.next(b).to_vec())
+            .collect();
+
+        assert_eq!(outputs, [vec![], vec![0], vec![1], vec![], vec![0], vec![]]);
+        assert!(automation.verify_failure_links());
+    }
+
+    #[test]
+    fn pattern_of_matched_id() {
+        let automation = Automation::build_with_patterns(["he", "she", "hers"].into_iter());
+        let matched: Vec<_> = automation
+            .find_iter("hers")
+            .map(|(id, _)| *automation.pattern(id))
+            .collect();
+
+        assert_eq!(matched, ["he", "hers"]);
+    }
+
+    #[test]
+    fn cloned_search_diverges() {
+        let automation = Automation::build(["ab", "cd"].into_iter());
+        let mut search = automation.search();
+        search.next(&'a');
+
+        let mut fork = search;
+        assert!(search.next(&'c').is_empty());
+        assert_eq!(search.next(&'d'), [1]);
+        assert_eq!(fork.current_depth(), 1);
+        assert_eq!(fork.next(&'b'), [0]);
+        assert_ne!(fork.position(), search.position());
+    }
+
+    #[test]
+    fn anchored_matches_only_at_start() {
+        let anchored = |patterns: [&str; 1], haystack: &str| {
+            let automation = Automation::build(patterns.into_iter());
+            let mut search = automation.search();
+            haystack
+                .chars()
+                .any(|c| !search.next_anchored(&c).is_empty())
+        };
+
+        assert!(anchored(["abc"], "abcdef"));
+        assert!(!anchored(["bc"], "abc"));
+        assert!(!anchored(["bc"], "abcbc"));
+    }
+
+    #[test]
+    fn duplicates_are_reported_once() {
+        let automation = Automation::build(["ab", "ab", "abc"].into_iter());
+        let matches: Vec<_> = automation.find_iter("abcab").collect();
+
+        assert_eq!(matches, [(0, 2), (2, 3), (0, 5)]);
+        assert_eq!(automation.duplicate_of(1), Some(0));
+        assert_eq!(automation.duplicate_of(0), None);
+        assert_eq!(automation.duplicate_of(2), None);
+    }
+
+    #[test]
+    fn try_build_without_patterns() {
+        let empty = Automation::<&str>::try_build(core::iter::empty());
+
+        assert_eq!(empty.err(), Some(BuildError::NoPatterns));
+        assert!(Automation::try_build(["a"].into_iter()).is_ok());
+    }
+
+    #[test]
+    fn direct_outputs_only_end_exactly() {
+        let automation = Automation::build(["he", "she", "hers"].into_iter());
+        let mut search = automation.search();
+
+        for c in "sh".chars() {
+            search.next(&c);
+        }
+        assert_eq!(search.next(&'e'), [1, 0]);
+        assert_eq!(search.direct_outputs(), [1]);
+
+        search.reset();
+        assert!(search.next(&'h').is_empty());
+        assert_eq!(search.next(&'e'), [0]);
+        assert_eq!(search.direct_outputs(), [0]);
+    }
+
+    #[test]
+    fn add_patterns_after_build() {
+        let mut automation = Automation::build(["he", "she"].into_iter());
+        assert_eq!(
+            automation.find_iter("ushers").collect::<Vec<_>>(),
+            [(1, 4), (0, 4)]
+        );
+
+        automation.add_patterns(["hers"].into_iter());
+
+        let matches: Vec<_> = automation.find_iter("ushers").collect();
+        assert_eq!(matches, [(1, 4), (0, 4), (2, 6)]);
+        assert_eq!(automation.num_patterns(), 3);
+        assert!(automation.verify_failure_links());
+    }
+
+    #[test]
+    fn incremental_build_matches_build() {
+        let patterns = ["he", "she", "his", "hers"];
+        let haystack = "ushers said his share of hers";
+
+        let mut automation = Automation::new();
+        for (id, pattern) in patterns.into_iter().enumerate() {
+            assert_eq!(automation.add_item(pattern), id);
+        }
+        automation.finalize();
+
+        let expected: Vec<_> = Automation::build(patterns.into_iter())
+            .find_iter(haystack)
+            .collect();
+        assert_eq!(automation.find_iter(haystack).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn walk_nodes_by_hand() {
+        let automation = Automation::build(["he", "she"].into_iter());
+        let walk = |prefix: &str| {
+            prefix
+                .chars()
+                .try_fold(0, |node, c| automation.goto(node, &c))
+        };
+
+        let she = walk("she").unwrap();
+        let he = walk("he").unwrap();
+
+        assert_eq!(automation.goto(0, &'e'), None);
+        assert_eq!(automation.goto(she, &'x'), None);
+        assert_eq!(automation.failure(she), he);
+        assert_eq!(automation.failure(walk("sh").unwrap()), walk("h").unwrap());
+        assert_eq!(automation.failure(he), 0);
+        assert_eq!(automation.outputs(she), [1, 0]);
+        assert_eq!(automation.outputs(he), [0]);
+    }
+
+    #[test]
+    fn lengths_of_outputs_ending_together() {
+        let automation = Automation::build(["he", "she"].into_iter());
+        let mut search = automation.search();
+        search.next(&'s');
+        search.next(&'h');
+
+        let lengths: Vec<_> = search.next_with_lengths(&'e').collect();
+        assert_eq!(lengths, [(1, 3), (0, 2)]);
+    }
+
+    #[test]
+    fn u32_symbols() {
+        let patterns: [&[u32]; 3] = [&[1000, 7], &[7, 1000], &[1000, 7, 1000]];
+        let automation = Automation::build(patterns.into_iter());
+
+        let mut search = automation.search();
+        let outputs: Vec<Vec<usize>> = [1000, 7, 1000, 7]
+            .iter()
+            .map(|symbol| search.next(symbol).to_vec())
+            .collect();
+        assert_eq!(outputs, [vec![], vec![0], vec![2, 1], vec![0]]);
+
+        let json = automation.dump().to_json();
+        assert!(json.contains("\"label\":\"1000\""));
+        assert!(json.contains("\"label\":\"7\""));
+    }
+
+    #[test]
+    fn depth_after_failure_jumps() {
+        let automation = Automation::build(["he", "she", "hers"].into_iter());
+        let mut search = automation.search();
+        let depths: Vec<_> = "shers"
+            .chars()
+            .map(|c| {
+                search.next(&c);
+                search.current_depth()
+            })
+            .collect();
+
+        // At `r`, the search falls back from `she` to `he`, then goes on to `her`.
+        assert_eq!(depths, [1, 2, 3, 3, 4]);
+    }
+
+    #[test]
+    fn values_of_matched_patterns() {
+        let automation =
+            Automation::build_with_values([("he", 3), ("she", 1), ("hers", 2)].into_iter());
+        let priorities: Vec<usize> = automation
+            .find_iter("ushers")
+            .map(|(id, _)| *automation.value(id))
+            .collect();
+
+        assert_eq!(priorities, [1, 3, 2]);
+    }
+
+    #[test]
+    fn contains_output_while_scanning() {
+        let automation = Automation::build(["he", "she", "hers"].into_iter());
+        let mut search = automation.search();
+        let he_ends: Vec<_> = "shers"
+            .chars()
+            .map(|c| {
+                search.next(&c);
+                search.contains_output(0)
+            })
+            .collect();
+
+        assert_eq!(he_ends, [false, false, true, false, false]);
+        assert!(search.contains_output(2));
+    }
+
+    #[test]
+    fn longest_output_at_end() {
+        let automation = Automation::build(["he", "hers", "ers"].into_iter());
+        let mut search = automation.search();
+        let longest: Vec<_> = "hers"
+            .chars()
+            .map(|c| {
+                search.next(&c);
+                search.longest_output()
+            })
+            .collect();
+
+        assert_eq!(longest, [None, Some(0), None, Some(1)]);
+    }
+
+    #[test]
+    fn sorted_outputs() {
+        let mut automation = Automation::build(["hers", "she", "he", "rs", "s"].into_iter());
+        automation.sort_outputs();
+
+        let outputs = |automation: &Automation<&str>| {
+            let mut search = automation.search();
+            "shers"
+                .chars()
+                .map(|c| search.next(&c).to_vec())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            outputs(&automation),
+            [vec![4], vec![], vec![2, 1], vec![], vec![4, 3, 0]]
+        );
+
+        automation.add_patterns(["ers"].into_iter());
+        assert_eq!(outputs(&automation)[4], [4, 3, 5, 0]);
+    }
+
+    #[test]
+    fn root_alphabet_holds_first_chars() {
+        let automation = Automation::build(["he", "she", "an", "hers"].into_iter());
+        let alphabet: alloc::collections::BTreeSet<_> =
+            automation.root_alphabet().copied().collect();
+
+        assert_eq!(alphabet, ['a', 'h', 's'].into());
+    }
+
+    #[test]
+    fn terminals_of_patterns() {
+        let automation = Automation::build(["she", "he", "she", "s"].into_iter());
+        let node = |prefix| automation.node_for_prefix(&prefix).unwrap();
+        let terminals: Vec<_> = automation.terminals().collect();
+
+        // The duplicate `"she"` has no terminal of its own.
+        assert_eq!(
+            terminals,
+            [(3, node("s")), (0, node("she")), (1, node("he"))]
+        );
+    }
+
+    #[test]
+    fn is_pattern_needs_exact_match() {
+        let automation = Automation::build(["he", "hers"].into_iter());
+
+        assert!(automation.is_pattern(&"he"));
+        assert!(automation.is_pattern(&"hers"));
+        assert!(!automation.is_pattern(&"h"));
+        assert!(!automation.is_pattern(&"her"));
+        assert!(!automation.is_pattern(&"herself"));
+    }
+
+    #[test]
+    fn build_with_length_limit() {
+        let patterns = ["he", "hello", "hi"];
+        let too_long = Automation::build_with_limits(patterns.into_iter(), 4);
+        assert_eq!(too_long.err(), Some(BuildError::PatternTooLong { len: 5 }));
+
+        let automation = Automation::build_with_limits(patterns.into_iter(), 5).unwrap();
+        assert_eq!(
+            automation.find_iter("hello").collect::<Vec<_>>(),
+            [(0, 2), (1, 5)]
+        );
+    }
+
+    #[test]
+    fn overlapping_and_nonoverlapping_searches() {
+        let automation = Automation::build(["he", "she", "hers", "s"].into_iter());
+        let haystack = "ushers said hershey";
+
+        let scan = |overlapping| {
+            let mut search = automation.search();
+            search.set_overlapping(overlapping);
+
+            let mut matches = Vec::new();
+            for (i, c) in haystack.char_indices() {
+                matches.extend(search.next(&c).iter().map(|&output| (output, i + 1)));
+            }
+            matches
+        };
+
+        let overlapping = scan(true);
+        assert_eq!(overlapping.len(), automation.count_matches(haystack));
+
+        let nonoverlapping = scan(false);
+        let expected: Vec<_> = automation
+            .find_iter_nonoverlapping(haystack)
+            .map(|(output, _, end)| (output, end))
+            .collect();
+        assert_eq!(nonoverlapping, expected);
+        assert!(nonoverlapping.len() < overlapping.len());
+    }
+
+    #[test]
+    fn merge_offsets_ids() {
+        let a = Automation::build_with_patterns(["he", "she"].into_iter());
+        let b = Automation::build_with_patterns(["hers", "he"].into_iter());
+        let merged = Automation::merge(a, b);
+
+        assert_eq!(merged.num_patterns(), 4);
+        assert_eq!(*merged.pattern(2), "hers");
+        assert_eq!(merged.duplicate_of(3), Some(0));
+        assert_eq!(
+            merged.find_iter("ushers").collect::<Vec<_>>(),
+            [(1, 4), (0, 4), (2, 6)]
+        );
+    }
+
+    #[test]
+    fn anchored_search_dies() {
+        let automation = Automation::build(["ab"].into_iter());
+        let mut search = automation.search();
+
+        search.next_anchored(&'a');
+        assert!(!search.is_dead());
+        assert!(search.next_anchored(&'x').is_empty());
+        assert!(search.is_dead());
+
+        // Dead searches ignore everything, even chars that would match from the root.
+        assert!(search.next_anchored(&'a').is_empty());
+        assert!(search.next_anchored(&'b').is_empty());
+        assert!(search.is_dead());
+
+        search.reset();
+        assert!(!search.is_dead());
+        search.next_anchored(&'a');
+        assert_eq!(search.next_anchored(&'b'), [0]);
+    }
+
+    #[test]
+    fn progress_reports() {
+        let patterns = (0..2500u32).map(|i| i.to_be_bytes().to_vec());
+        let mut reports = Vec::new();
+        let automation =
+            Automation::build_with_progress(patterns, |inserted| reports.push(inserted));
+
+        assert_eq!(reports, [1024, 2048, 2500, 2500]);
+        assert_eq!(automation.num_patterns(), 2500);
+    }
+
+    #[test]
+    fn clear_and_rebuild() {
+        let mut automation = Automation::build_with_patterns(["he", "she", "he"].into_iter());
+        automation.clear();
+
+        assert_eq!(automation.num_patterns(), 0);
+        assert_eq!(automation.num_nodes(), 1);
+        assert!(!automation.is_match("ushers"));
+
+        automation.add_patterns(["hers", "she"].into_iter());
+
+        assert_eq!(automation.num_patterns(), 2);
+        assert_eq!(automation.duplicate_of(1), None);
+        assert_eq!(*automation.pattern(0), "hers");
+        assert_eq!(
+            automation.find_iter("ushers").collect::<Vec<_>>(),
+            [(1, 4), (0, 6)]
+        );
+    }
+
+    #[test]
+    fn continuations_of_prefix() {
+        let automation = Automation::build(["he", "her", "hex", "hers", "ha"].into_iter());
+        let he = automation.node_for_prefix(&"he").unwrap();
+
+        let children: Vec<_> = ('a'..='z')
+            .filter(|c| automation.goto(he, c).is_some())
+            .collect();
+        assert_eq!(children, ['r', 'x']);
+        assert_eq!(
+            automation.goto(he, &'r'),
+            automation.node_for_prefix(&"her")
+        );
+        assert_eq!(automation.node_for_prefix(&""), Some(0));
+        assert_eq!(automation.node_for_prefix(&"hz"), None);
+    }
+
+    #[test]
+    fn sorted_build_ignores_input_order() {
+        let a = Automation::build_sorted(["she", "he", "hers"].into_iter());
+        let b = Automation::build_sorted(["hers", "she", "he"].into_iter());
+        let haystack = "ushers";
+
+        // Sorted, the patterns are `"he"`, `"hers"` and `"she"`.
+        let expected = [(2, 4), (0, 4), (1, 6)];
+        assert_eq!(a.find_iter(haystack).collect::<Vec<_>>(), expected);
+        assert_eq!(b.find_iter(haystack).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn feed_slice_across_halves() {
+        let automation = Automation::build([&b"abc"[..], b"cd"].into_iter());
+        let mut search = automation.search();
+        let mut matches = Vec::new();
+
+        search.feed_slice(b"xab", |offset, output| matches.push((0, offset, output)));
+        search.feed_slice(b"cdab", |offset, output| matches.push((1, offset, output)));
+
+        // Offsets are relative to the slice the match ends in.
+        assert_eq!(matches, [(1, 0, 0), (1, 1, 1)]);
+        assert_eq!(search.position(), 7);
+    }
+
+    #[test]
+    fn reconstructed_patterns() {
+        let automation = Automation::build(["he", "", "she", "he"].into_iter());
+        let chars = |pattern: &str| Some(pattern.chars().collect::<Vec<_>>());
+
+        assert_eq!(
+            automation.patterns(),
+            [chars("he"), chars(""), chars("she"), chars("he")]
+        );
+    }
+
+    #[test]
+    fn position_counts_fed_chars() {
+        let automation = Automation::build(["ab"].into_iter());
+        let mut search = automation.search();
+
+        for (calls, c) in "xabab".chars().enumerate() {
+            assert_eq!(search.position(), calls);
+            assert_eq!(search.next_positioned(&c).0, calls);
+        }
+        assert_eq!(search.position(), 5);
+
+        search.reset();
+        assert_eq!(search.position(), 0);
+    }
+
+    #[test]
+    fn values_of_outputs() {
+        let automation = Automation::build_with_values(
+            [
+                ("he", "pronoun"),
+                ("she", "pronoun"),
+                ("hers", "possessive"),
+            ]
+            .into_iter(),
+        );
+        let mut search = automation.search();
+        let categories: Vec<Vec<&'static str>> =
+            "shers".chars().map(|c| search.next_values(&c)).collect();
+
+        assert_eq!(
+            categories,
+            [
+                vec![],
+                vec![],
+                vec!["pronoun", "pronoun"],
+                vec![],
+                vec!["possessive"]
+            ]
+        );
+    }
+
+    #[test]
+    fn build_with_capacity_like_build() {
+        let patterns = ["he", "she", "his", "hers"];
+        let haystack = "ushers said his share of hers";
+
+        let automation = Automation::build_with_capacity(patterns.into_iter(), 12);
+        let expected = Automation::build(patterns.into_iter());
+
+        assert!(automation.nodes.capacity() >= 12);
+        assert_eq!(automation.num_nodes(), expected.num_nodes());
+        assert_eq!(
+            automation.find_iter(haystack).collect::<Vec<_>>(),
+            expected.find_iter(haystack).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn retain_patterns_renumbers() {
+        let automation = Automation::build_with_patterns(["he", "she", "hers"].into_iter());
+        let retained = automation.retain_patterns(|id, _| id != 1);
+
+        assert_eq!(retained.num_patterns(), 2);
+        assert_eq!(*retained.pattern(1), "hers");
+        assert_eq!(
+            retained.find_iter("ushers").collect::<Vec<_>>(),
+            [(0, 4), (1, 6)]
+        );
+
+        let without_he = automation.retain_patterns(|_, pattern| *pattern != "he");
+        assert_eq!(
+            without_he.find_iter("ushers").collect::<Vec<_>>(),
+            [(0, 4), (1, 6)]
+        );
+    }
+
+    #[test]
+    fn truncated_outputs_keep_shortest() {
+        let mut automation = Automation::build(["she", "he", "e"].into_iter());
+        assert_eq!(automation.find_iter("she").count(), 3);
+
+        automation.truncate_outputs(1);
+
+        assert_eq!(
+            automation.find_iter("she he").collect::<Vec<_>>(),
+            [(2, 3), (2, 6)]
+        );
+        automation.add_patterns(["sh"].into_iter());
+        assert_eq!(
+            automation.find_iter("she").collect::<Vec<_>>(),
+            [(3, 2), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn last_output_count_follows_next() {
+        let automation = Automation::build(["he", "she", "hers"].into_iter());
+        let mut search = automation.search();
+        assert_eq!(search.last_output_count(), 0);
+
+        let counts: Vec<_> = "shers"
+            .chars()
+            .map(|c| {
+                let len = search.next(&c).len();
+                assert_eq!(search.last_output_count(), len);
+                len
+            })
+            .collect();
+        assert_eq!(counts, [0, 0, 2, 0, 1]);
+
+        search.reset();
+        assert_eq!(search.last_output_count(), 0);
+    }
+
+    #[test]
+    fn would_match_does_not_feed() {
+        let automation = Automation::build(["ab"].into_iter());
+        let mut search = automation.search();
+        search.next(&'a');
+
+        assert!(search.would_match(&'b'));
+        assert!(!search.would_match(&'a'));
+        assert_eq!(search.position(), 1);
+        assert_eq!(search.current_depth(), 1);
+        assert_eq!(search.last_output_count(), 0);
+
+        assert_eq!(search.next(&'b'), [0]);
     }
 }
@@ -0,0 +1,172 @@
+// Maps and sets used by the automation: hash based with `std`, ordered ones without it.
+
+use core::{
+    hash::{BuildHasher, Hasher},
+    mem,
+};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::{btree_map::Iter, BTreeMap as Map, BTreeSet as Set};
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{hash_map::Iter, HashMap as Map, HashSet as Set};
+
+// Chars used as goto keys: hashed with `std`, ordered without it. Public but not
+// exported, so that public impls can be bounded on it without naming it.
+#[cfg(feature = "std")]
+pub trait Key: Eq + core::hash::Hash {}
+
+#[cfg(feature = "std")]
+impl<C: Eq + core::hash::Hash> Key for C {}
+
+#[cfg(not(feature = "std"))]
+pub trait Key: Eq + core::hash::Hash + Ord {}
+
+#[cfg(not(feature = "std"))]
+impl<C: Eq + core::hash::Hash + Ord> Key for C {}
+
+/// Hasher used by default for the goto maps of nodes with many children.
+///
+/// With `std` it is randomly seeded, like the default hasher of `HashMap`.
+/// Without it goto maps are ordered, so it never hashes anything.
+#[derive(Clone, Default, Debug)]
+pub struct DefaultHashBuilder {
+    #[cfg(feature = "std")]
+    state: std::collections::hash_map::RandomState,
+}
+
+impl BuildHasher for DefaultHashBuilder {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        DefaultHasher {
+            #[cfg(feature = "std")]
+            inner: self.state.build_hasher(),
+            #[cfg(not(feature = "std"))]
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+/// Hasher built by [`DefaultHashBuilder`].
+#[derive(Clone, Debug)]
+pub struct DefaultHasher {
+    #[cfg(feature = "std")]
+    inner: std::collections::hash_map::DefaultHasher,
+    // FNV-1a, which needs no randomness.
+    #[cfg(not(feature = "std"))]
+    state: u64,
+}
+
+#[cfg(not(feature = "std"))]
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+#[cfg(not(feature = "std"))]
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+impl Hasher for DefaultHasher {
+    #[cfg(feature = "std")]
+    fn write(&mut self, bytes: &[u8]) {
+        self.inner.write(bytes);
+    }
+
+    #[cfg(feature = "std")]
+    fn finish(&self) -> u64 {
+        self.inner.finish()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.state = (self.state ^ u64::from(b)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+/// Hashers that can be used for the goto maps of nodes with many children,
+/// see [`Automation::build_with_hasher`](crate::Automation::build_with_hasher).
+///
+/// This is any cloneable [`BuildHasher`]. Without `std` goto maps are ordered,
+/// so the hasher is never used.
+pub trait HashBuilder: BuildHasher + Clone {}
+
+impl<S: BuildHasher + Clone> HashBuilder for S {}
+
+// Goto map of a node with many children.
+#[cfg(feature = "std")]
+pub(crate) type LargeMap<K, S> = Map<K, usize, S>;
+
+#[cfg(not(feature = "std"))]
+pub(crate) struct LargeMap<K, S> {
+    map: Map<K, usize>,
+    hasher: core::marker::PhantomData<S>,
+}
+
+#[cfg(not(feature = "std"))]
+impl<K: Ord, S> LargeMap<K, S> {
+    pub fn with_hasher(_hasher: S) -> Self {
+        Self {
+            map: Map::new(),
+            hasher: core::marker::PhantomData,
+        }
+    }
+
+    pub fn get(&self, k: &K) -> Option<&usize> {
+        self.map.get(k)
+    }
+
+    pub fn insert(&mut self, k: K, v: usize) -> Option<usize> {
+        self.map.insert(k, v)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<K, S> LargeMap<K, S> {
+    pub fn iter(&self) -> Iter<'_, K, usize> {
+        self.map.iter()
+    }
+}
+
+// B-trees allocate their nodes as they grow, so there is nothing to shrink.
+#[cfg(not(feature = "std"))]
+impl<K, S> LargeMap<K, S> {
+    pub fn shrink_to_fit(&mut self) {}
+}
+
+// Releases the excess capacity of `map`, which only hash maps have.
+#[cfg(feature = "std")]
+pub(crate) fn shrink_to_fit<K: Eq + core::hash::Hash, V>(map: &mut Map<K, V>) {
+    map.shrink_to_fit();
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn shrink_to_fit<K, V>(_map: &mut Map<K, V>) {}
+
+// Estimates the heap memory used by the entries of `map`, in bytes.
+#[cfg(feature = "std")]
+pub(crate) fn heap_bytes<K, V, S>(map: &Map<K, V, S>) -> usize {
+    // Every hash map bucket also has a control byte.
+    map.capacity() * (mem::size_of::<(K, V)>() + 1)
+}
+
+// Estimates the heap memory used by the entries of `map`, in bytes.
+#[cfg(not(feature = "std"))]
+pub(crate) fn heap_bytes<K, V>(map: &Map<K, V>) -> usize {
+    // B-tree nodes are not exposed, so only the entries are counted.
+    map.len() * mem::size_of::<(K, V)>()
+}
+
+// Estimates the heap memory used by the entries of a large goto map, in bytes.
+pub(crate) fn large_heap_bytes<K, S>(map: &LargeMap<K, S>) -> usize {
+    #[cfg(feature = "std")]
+    let bytes = heap_bytes(map);
+
+    #[cfg(not(feature = "std"))]
+    let bytes = heap_bytes(&map.map);
+
+    bytes
+}
@@ -0,0 +1,90 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{Automation, Pattern};
+
+impl<P: Pattern, V, S> Automation<P, V, S> {
+    /// Groups the output IDs of patterns that occur inside one another,
+    /// e.g. `"he"` and `"hers"`. Relations are transitive, so `"he"`, `"her"` and `"hers"`
+    /// end up in the same group even if `"she"` only contains `"he"`.
+    ///
+    /// Each group is sorted and groups are ordered by their lowest ID.
+    /// Patterns not related to any other one are left out.
+    pub fn overlapping_groups(&self) -> Vec<Vec<usize>> {
+        let mut parents: Vec<usize> = (0..self.output_cnt).collect();
+
+        // Identical patterns contain each other.
+        for (&duplicate, &original) in &self.duplicates {
+            union(&mut parents, duplicate, original);
+        }
+
+        // Every output reachable from a node on the trie path of a pattern,
+        // through the merged outputs, occurs inside that pattern.
+        let mut path: Vec<usize> = Vec::new();
+        let mut stack = vec![0];
+
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            path.truncate(node.depth);
+            path.push(node_idx);
+
+            for &output in &node.direct_outputs {
+                for &on_path in &path {
+                    for &inner in &self.nodes[on_path].outputs {
+                        union(&mut parents, output, inner);
+                    }
+                }
+            }
+
+            stack.extend(node.goto.values().copied());
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of: Vec<Option<usize>> = vec![None; self.output_cnt];
+
+        for output in 0..self.output_cnt {
+            let root = find(&mut parents, output);
+
+            match group_of[root] {
+                Some(group) => groups[group].push(output),
+                None => {
+                    group_of[root] = Some(groups.len());
+                    groups.push(vec![output]);
+                }
+            }
+        }
+
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+}
+
+fn find(parents: &mut [usize], mut x: usize) -> usize {
+    while parents[x] != x {
+        parents[x] = parents[parents[x]];
+        x = parents[x];
+    }
+
+    x
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let a = find(parents, a);
+    let b = find(parents, b);
+    parents[a.max(b)] = a.min(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_of_contained_patterns() {
+        let patterns = ["he", "she", "hers", "her", "xyz", "ab", "yz"];
+        let automation = Automation::build(patterns.into_iter());
+
+        assert_eq!(
+            automation.overlapping_groups(),
+            [vec![0, 1, 2, 3], vec![4, 6]]
+        );
+    }
+}
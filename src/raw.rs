@@ -0,0 +1,438 @@
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{Automation, AutomationNode, HashBuilder, Pattern};
+
+/// The goto transitions, failure link and outputs of a node,
+/// see [`Automation::into_nodes`].
+pub type RawNode<C> = (Vec<(C, usize)>, usize, Vec<usize>);
+
+impl<P: Pattern, V, S: HashBuilder> Automation<P, V, S> {
+    /// Exports the nodes as plain data, indexed like the nodes themselves, the root being `0`.
+    ///
+    /// The goto transitions are listed in no particular order. The outputs
+    /// are the ones reported by searches, i.e. including the ones merged from failure
+    /// link targets. Values, retained patterns and duplicates are not exported.
+    pub fn into_nodes(self) -> Vec<RawNode<P::Char>>
+    where
+        P::Char: Clone,
+    {
+        self.nodes
+            .into_iter()
+            .map(|node| {
+                let goto = node
+                    .goto
+                    .iter()
+                    .map(|(c, &next)| (c.clone(), next))
+                    .collect();
+                (goto, node.failure, node.outputs)
+            })
+            .collect()
+    }
+}
+
+impl<P: Pattern> Automation<P> {
+    /// Rebuilds an automation from nodes exported by [`Automation::into_nodes`].
+    ///
+    /// The patterns ending at a node are the outputs it does not share with its failure
+    /// link target. From these, the failure links and merged outputs are computed again,
+    /// so searches behave exactly like the ones of the exported automation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `nodes` is empty, if a node has several goto transitions for the same
+    /// char, if a goto transition or failure link leads to a node that does not exist,
+    /// if the goto transitions do not form a tree rooted at `0` spanning all nodes,
+    /// or if a failure link does not lead to a node closer to the root.
+    pub fn from_nodes(nodes: Vec<RawNode<P::Char>>) -> Self {
+        let mut gotos = Vec::with_capacity(nodes.len());
+        let mut outputs = Vec::with_capacity(nodes.len());
+
+        for (goto, failure, node_outputs) in nodes {
+            gotos.push(goto);
+            outputs.push((failure, node_outputs));
+        }
+
+        let mut automation = Self::new();
+
+        if let Err(e) = automation.set_gotos(gotos) {
+            panic!("{}", e);
+        }
+
+        for (node_idx, &(failure, _)) in outputs.as_slice().iter().enumerate() {
+            assert!(
+                failure < automation.nodes.len(),
+                "failure link {} does not exist",
+                failure
+            );
+            assert!(
+                node_idx == 0 || automation.nodes[failure].depth < automation.nodes[node_idx].depth,
+                "failure link of node {} does not lead closer to the root",
+                node_idx
+            );
+        }
+        assert_eq!(outputs[0].0, 0, "the failure link of the root must be 0");
+
+        let output_cnt = outputs
+            .as_slice()
+            .iter()
+            .flat_map(|(_, node_outputs)| node_outputs.as_slice().iter())
+            .max()
+            .map_or(0, |&output| output + 1);
+        automation.output_cnt = output_cnt;
+        automation.pattern_lens = vec![0; output_cnt];
+        automation.values = vec![(); output_cnt];
+
+        for (node_idx, (failure, node_outputs)) in outputs.as_slice().iter().enumerate() {
+            let inherited: &[usize] = if node_idx == 0 {
+                &[]
+            } else {
+                &outputs[*failure].1
+            };
+
+            for &output in node_outputs {
+                if !inherited.contains(&output) {
+                    automation.add_direct_output(node_idx, output);
+                }
+            }
+        }
+
+        automation.find_class_outputs();
+        automation.build_failure();
+        automation
+    }
+}
+
+impl<P: Pattern, V, S: HashBuilder> Automation<P, V, S> {
+    // Replaces the nodes with the ones given by their goto transitions, after checking
+    // that these form a tree rooted at `0` spanning all nodes, and computes their depths.
+    // Outputs and failure links are left to the caller.
+    fn set_gotos(&mut self, gotos: Vec<Vec<(P::Char, usize)>>) -> Result<(), String> {
+        if gotos.is_empty() {
+            return Err("the root node is missing".to_string());
+        }
+
+        let node_cnt = gotos.len();
+        self.nodes.clear();
+        self.nodes.resize_with(node_cnt, || AutomationNode::new(0));
+
+        for (node_idx, goto) in gotos.into_iter().enumerate() {
+            for (c, next) in goto {
+                if next >= node_cnt {
+                    return Err(format!("goto target {} does not exist", next));
+                }
+                if self.nodes[node_idx].contains(&c) {
+                    return Err(format!(
+                        "node {} has several goto transitions for the same char",
+                        node_idx
+                    ));
+                }
+                self.nodes[node_idx].add_child(c, next, &self.hasher);
+            }
+        }
+
+        // Depths are only known once the trie paths are known. Every node must be entered
+        // exactly once, a cycle would otherwise keep the queue from ever running empty.
+        let mut visited = vec![false; node_cnt];
+        visited[0] = true;
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+
+        while let Some(node_idx) = queue.pop_front() {
+            let depth = self.nodes[node_idx].depth + 1;
+            let children: Vec<usize> = self.nodes[node_idx].goto.values().copied().collect();
+
+            for next in children {
+                if visited[next] {
+                    return Err(format!("node {} has more than one parent", next));
+                }
+                visited[next] = true;
+                self.nodes[next].depth = depth;
+                queue.push_back(next);
+            }
+        }
+
+        match visited.as_slice().iter().position(|&visited| !visited) {
+            Some(node_idx) => Err(format!("node {} is not reachable from the root", node_idx)),
+            None => Ok(()),
+        }
+    }
+
+    // Marks the outputs ending at several nodes as the ones of class patterns.
+    fn find_class_outputs(&mut self) {
+        let mut ends = vec![0usize; self.output_cnt];
+
+        for node in &self.nodes {
+            for &output in &node.direct_outputs {
+                ends[output] += 1;
+            }
+        }
+
+        self.class_outputs = (0..self.output_cnt)
+            .filter(|&output| ends[output] > 1)
+            .collect();
+    }
+
+    // Adds `output` as a pattern ending exactly at `node_idx`, whose depth is its length.
+    fn add_direct_output(&mut self, node_idx: usize, output: usize) {
+        let node = &mut self.nodes[node_idx];
+        node.add_output(output);
+        self.pattern_lens[output] = node.depth;
+    }
+}
+
+#[cfg(feature = "serde")]
+mod persist {
+    use alloc::{format, string::String, vec, vec::Vec};
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{map::Map, Automation, HashBuilder, Pattern};
+
+    // The goto transitions and direct outputs of a node.
+    type TrieNode<C> = (Vec<(C, usize)>, Vec<usize>);
+    type TrieNodeRef<'a, C> = (Vec<(&'a C, usize)>, &'a [usize]);
+
+    // The serialized form of an automation: the trie as the goto transitions
+    // and the direct outputs of every node. Everything derived from it, like
+    // the failure links and depths, is computed again while deserializing.
+    #[derive(Serialize)]
+    #[serde(rename = "Automation")]
+    struct AutomationRef<'a, P, C, V> {
+        nodes: Vec<TrieNodeRef<'a, C>>,
+        output_cnt: usize,
+        duplicates: Vec<(usize, usize)>,
+        patterns: &'a Option<Vec<P>>,
+        values: &'a [V],
+        sorted_outputs: bool,
+        max_outputs: usize,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "Automation")]
+    struct AutomationData<P, C, V> {
+        nodes: Vec<TrieNode<C>>,
+        output_cnt: usize,
+        duplicates: Vec<(usize, usize)>,
+        patterns: Option<Vec<P>>,
+        values: Vec<V>,
+        sorted_outputs: bool,
+        max_outputs: usize,
+    }
+
+    impl<P, V, S> Serialize for Automation<P, V, S>
+    where
+        P: Pattern + Serialize,
+        P::Char: Serialize,
+        V: Serialize,
+        S: HashBuilder,
+    {
+        fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+            let mut duplicates: Vec<(usize, usize)> =
+                self.duplicates.iter().map(|(&k, &v)| (k, v)).collect();
+            duplicates.sort_unstable();
+
+            AutomationRef {
+                nodes: self
+                    .nodes
+                    .iter()
+                    .map(|node| {
+                        let goto = node.goto.iter().map(|(c, &next)| (c, next)).collect();
+                        (goto, node.direct_outputs.as_slice())
+                    })
+                    .collect(),
+                output_cnt: self.output_cnt,
+                duplicates,
+                patterns: &self.patterns,
+                values: &self.values,
+                sorted_outputs: self.sorted_outputs,
+                max_outputs: self.max_outputs,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, P, V, S> Deserialize<'de> for Automation<P, V, S>
+    where
+        P: Pattern + Deserialize<'de>,
+        P::Char: Deserialize<'de>,
+        V: Deserialize<'de>,
+        S: HashBuilder + Default,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let data = AutomationData::deserialize(deserializer)?;
+            Self::from_data(data).map_err(de::Error::custom)
+        }
+    }
+
+    impl<P: Pattern, V, S: HashBuilder + Default> Automation<P, V, S> {
+        // Rebuilds the automation through the same checks as `from_nodes`, so that
+        // a corrupted or tampered input fails instead of breaking searches.
+        fn from_data(data: AutomationData<P, P::Char, V>) -> Result<Self, String> {
+            let output_cnt = data.output_cnt;
+
+            if data.values.len() != output_cnt {
+                return Err(format!(
+                    "expected {} values, got {}",
+                    output_cnt,
+                    data.values.len()
+                ));
+            }
+            if let Some(patterns) = &data.patterns {
+                if patterns.len() != output_cnt {
+                    return Err(format!(
+                        "expected {} patterns, got {}",
+                        output_cnt,
+                        patterns.len()
+                    ));
+                }
+            }
+
+            let mut automation = Self::empty(S::default());
+            let (gotos, outputs): (Vec<_>, Vec<_>) = data.nodes.into_iter().unzip();
+            automation.set_gotos(gotos)?;
+            automation.output_cnt = output_cnt;
+            automation.pattern_lens = vec![0; output_cnt];
+
+            // Every pattern ends at a node, unless it is a duplicate of one that does.
+            let mut placed = vec![false; output_cnt];
+
+            for (node_idx, node_outputs) in outputs.into_iter().enumerate() {
+                for output in node_outputs {
+                    if output >= output_cnt {
+                        return Err(format!("output {} does not exist", output));
+                    }
+                    if automation.nodes[node_idx].direct_outputs.contains(&output) {
+                        return Err(format!(
+                            "output {} is repeated at node {}",
+                            output, node_idx
+                        ));
+                    }
+
+                    // Class patterns end at several nodes, all at the same depth.
+                    let depth = automation.nodes[node_idx].depth;
+                    if placed[output] && automation.pattern_lens[output] != depth {
+                        return Err(format!("output {} ends at different depths", output));
+                    }
+
+                    placed[output] = true;
+                    automation.add_direct_output(node_idx, output);
+                }
+            }
+
+            let mut duplicates = Map::new();
+
+            for (duplicate, original) in data.duplicates {
+                if duplicate >= output_cnt || original >= output_cnt {
+                    return Err(format!(
+                        "duplicate {} of {} does not exist",
+                        duplicate, original
+                    ));
+                }
+                if placed[duplicate] || !placed[original] || duplicates.contains_key(&duplicate) {
+                    return Err(format!(
+                        "output {} is not a duplicate of {}",
+                        duplicate, original
+                    ));
+                }
+
+                automation.pattern_lens[duplicate] = automation.pattern_lens[original];
+                duplicates.insert(duplicate, original);
+            }
+
+            if let Some(output) =
+                (0..output_cnt).find(|&output| !placed[output] && !duplicates.contains_key(&output))
+            {
+                return Err(format!("output {} does not end at any node", output));
+            }
+
+            automation.duplicates = duplicates;
+            automation.patterns = data.patterns;
+            automation.values = data.values;
+            automation.sorted_outputs = data.sorted_outputs;
+            automation.max_outputs = data.max_outputs;
+            automation.find_class_outputs();
+            automation.build_failure();
+
+            Ok(automation)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodes_round_trip() {
+        let patterns = ["he", "she", "his", "hers"];
+        let haystack = "ushers said his share of hershey";
+        let expected: Vec<_> = Automation::build(patterns.into_iter())
+            .find_iter(haystack)
+            .collect();
+
+        let nodes = Automation::build(patterns.into_iter()).into_nodes();
+        let automation: Automation<&str> = Automation::from_nodes(nodes);
+
+        assert_eq!(automation.find_iter(haystack).collect::<Vec<_>>(), expected);
+        assert_eq!(automation.num_patterns(), patterns.len());
+        assert_eq!(automation.pattern_len(3), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "more than one parent")]
+    fn goto_cycle_is_rejected() {
+        let mut nodes = Automation::build(["ab"].into_iter()).into_nodes();
+        let b = nodes[1].0[0].1;
+        nodes[b].0.push(('c', 0));
+
+        let _: Automation<&str> = Automation::from_nodes(nodes);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_keeps_options_and_values() {
+        let mut automation =
+            Automation::build_with_values([("he", 1), ("she", 2), ("he", 3)].into_iter());
+        automation.sort_outputs();
+        let json = serde_json::to_string(&automation).unwrap();
+        let restored: Automation<&str, i32> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.duplicate_of(2), Some(0));
+        assert_eq!(*restored.value(2), 3);
+        assert_eq!(restored.pattern_len(1), 3);
+        assert_eq!(
+            restored.find_iter("she").collect::<Vec<_>>(),
+            [(0, 3), (1, 3)]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_tampered_tries() {
+        let automation = Automation::build(["ab", "b"].into_iter());
+        let json: serde_json::Value = serde_json::to_value(&automation).unwrap();
+        let node_of = |path: &'static str| automation.node_for_prefix(&path).unwrap();
+
+        // A goto transition from "ab" back to the root.
+        let mut cycle = json.clone();
+        cycle["nodes"][node_of("ab")][0] = serde_json::json!([["c", 0]]);
+        let err = serde_json::from_value::<Automation<String>>(cycle)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("more than one parent"));
+
+        let mut out_of_range = json.clone();
+        out_of_range["nodes"][node_of("b")][0] = serde_json::json!([["c", 9]]);
+        assert!(serde_json::from_value::<Automation<String>>(out_of_range).is_err());
+
+        let mut unknown_output = json;
+        unknown_output["nodes"][node_of("b")][1] = serde_json::json!([5]);
+        assert!(serde_json::from_value::<Automation<String>>(unknown_output).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use alloc::{borrow::Cow, string::String};
+
+use crate::{Automation, HashBuilder, Pattern};
+
+impl<P: Pattern<Char = char>, V, S: HashBuilder> Automation<P, V, S> {
+    /// Replaces every non-overlapping match with `replacements[pattern_id]`,
+    /// see [`Automation::find_iter_nonoverlapping`] for which matches are picked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer replacements than patterns.
+    pub fn replace_all(&self, haystack: &str, replacements: &[&str]) -> String {
+        self.replace_all_cow(haystack, replacements).into_owned()
+    }
+
+    /// Same as [`Automation::replace_all`], but borrows `haystack` instead of copying it
+    /// if nothing matches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer replacements than patterns.
+    pub fn replace_all_cow<'h>(&self, haystack: &'h str, replacements: &[&str]) -> Cow<'h, str> {
+        assert!(
+            replacements.len() >= self.output_cnt,
+            "expected a replacement for each of {} patterns, got {}",
+            self.output_cnt,
+            replacements.len()
+        );
+
+        let mut matches = self.find_iter_nonoverlapping(haystack).peekable();
+
+        if matches.peek().is_none() {
+            return Cow::Borrowed(haystack);
+        }
+
+        let mut result = String::with_capacity(haystack.len());
+        let mut last = 0;
+
+        for (output, start, end) in matches {
+            result.push_str(&haystack[last..start]);
+            result.push_str(replacements[output]);
+            last = end;
+        }
+
+        result.push_str(&haystack[last..]);
+        Cow::Owned(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_all_picks_longest() {
+        let automation = Automation::build(["he", "she"].into_iter());
+
+        assert_eq!(
+            automation.replace_all("she said he left", &["X", "Y"]),
+            "Y said X left"
+        );
+    }
+
+    #[test]
+    fn replace_all_cow_borrows_without_matches() {
+        let automation = Automation::build(["he", "she"].into_iter());
+
+        let unchanged = automation.replace_all_cow("no match", &["X", "Y"]);
+        assert!(matches!(unchanged, Cow::Borrowed("no match")));
+
+        let replaced = automation.replace_all_cow("ushers", &["X", "Y"]);
+        assert!(matches!(replaced, Cow::Owned(ref s) if s == "uYrs"));
+    }
+}
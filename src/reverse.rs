@@ -0,0 +1,53 @@
+use alloc::vec::Vec;
+
+use crate::{Automation, Pattern};
+
+/// Pattern wrapper matching the pattern backwards, for scanning input from its end.
+///
+/// Feeding the chars of an input in reverse order to an automation of reversed patterns
+/// finds the same matches as the forward automation, with start and end swapped.
+///
+/// [`Pattern::iter`] only yields chars forward, so every iteration first buffers
+/// all chars of the pattern. This allocates once per pattern while building,
+/// searching is not affected.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Reversed<P>(pub P);
+
+impl<P: Pattern> Pattern for Reversed<P> {
+    type Char = P::Char;
+
+    fn iter(&self) -> impl Iterator<Item = Self::Char> {
+        let chars: Vec<P::Char> = self.0.iter().collect();
+        chars.into_iter().rev()
+    }
+
+    fn fold(c: &Self::Char) -> Option<Self::Char> {
+        P::fold(c)
+    }
+}
+
+impl<P: Pattern> Automation<Reversed<P>> {
+    /// Builds an automation matching the patterns backwards, see [`Reversed`].
+    pub fn build_reversed(items: impl Iterator<Item = P>) -> Self {
+        Self::build(items.map(Reversed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reversed_patterns_match_backwards() {
+        let automation = Automation::build_reversed(["he", "hers"].into_iter());
+
+        assert!(automation.is_match("eh"));
+        assert!(!automation.is_match("he"));
+
+        // Scanning `"ushers"` from its end, both patterns are found at their first char.
+        let matches = automation.run("ushers".chars().rev());
+        assert_eq!(matches, [(3, 1), (3, 0)]);
+    }
+}
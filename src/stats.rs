@@ -0,0 +1,232 @@
+use alloc::{vec, vec::Vec};
+use core::mem;
+
+use crate::{map, Automation, AutomationNode, HashBuilder, Pattern};
+
+/// Aggregate statistics of the goto transitions, see [`Automation::transition_stats`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TransitionStats {
+    /// Total number of goto transitions.
+    pub total: usize,
+    /// Highest number of goto transitions out of a single node.
+    pub max_degree: usize,
+    /// Average number of goto transitions out of a node, leaves included.
+    pub avg_degree: f64,
+}
+
+impl<P: Pattern, V, S: HashBuilder> Automation<P, V, S> {
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Counts the nodes reachable from the root through goto transitions.
+    ///
+    /// Every node of a well formed trie is reachable, so this always equals
+    /// [`Automation::num_nodes`] unless the build code is broken.
+    pub fn reachable_node_count(&self) -> usize {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![0];
+        let mut cnt = 0;
+
+        while let Some(node_idx) = stack.pop() {
+            if visited[node_idx] {
+                continue;
+            }
+
+            visited[node_idx] = true;
+            cnt += 1;
+            stack.extend(self.nodes[node_idx].goto.values().copied());
+        }
+
+        cnt
+    }
+
+    /// Checks every failure link against the longest proper suffix of its node
+    /// present in the trie, found independently by walking each suffix from the root.
+    ///
+    /// This always returns `true` unless the build code is broken. It takes time
+    /// quadratic in the pattern lengths, so it is only meant for testing.
+    pub fn verify_failure_links(&self) -> bool {
+        let mut path: Vec<&P::Char> = Vec::new();
+        let mut stack: Vec<(usize, Option<&P::Char>)> = vec![(0, None)];
+
+        while let Some((node_idx, c)) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            path.truncate(node.depth.saturating_sub(1));
+            path.extend(c);
+
+            let expected = (1..path.len())
+                .find_map(|start| {
+                    path[start..]
+                        .iter()
+                        .try_fold(0, |idx, c| self.nodes[idx].enter_child(c))
+                })
+                .unwrap_or(0);
+
+            if node.failure != expected {
+                return false;
+            }
+
+            stack.extend(node.goto.iter().map(|(c, &next)| (next, Some(c))));
+        }
+
+        true
+    }
+
+    /// Returns the longest chain of failure links from any node to the root,
+    /// which bounds the failure hops a single [`AutomationSearch::next`] call can take.
+    ///
+    /// [`AutomationSearch::next`]: crate::AutomationSearch::next
+    pub fn max_failure_depth(&self) -> usize {
+        // Failure links always lead to shallower nodes,
+        // so their hop counts are known when visiting nodes by increasing depth.
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_unstable_by_key(|&node_idx| self.nodes[node_idx].depth);
+
+        let mut hops = vec![0; self.nodes.len()];
+
+        for node_idx in order.into_iter().skip(1) {
+            hops[node_idx] = hops[self.nodes[node_idx].failure] + 1;
+        }
+
+        hops.into_iter().max().unwrap_or(0)
+    }
+
+    /// Computes the number of goto transitions and how they are spread over the nodes.
+    ///
+    /// Since every node but the root is entered by exactly one transition,
+    /// `total` is always [`Automation::num_nodes`] minus one.
+    pub fn transition_stats(&self) -> TransitionStats {
+        let mut total = 0;
+        let mut max_degree = 0;
+
+        for node in &self.nodes {
+            let degree = node.goto.keys().count();
+            total += degree;
+            max_degree = max_degree.max(degree);
+        }
+
+        TransitionStats {
+            total,
+            max_degree,
+            avg_degree: total as f64 / self.nodes.len() as f64,
+        }
+    }
+
+    /// Returns the number of inserted patterns, duplicates included.
+    pub fn num_patterns(&self) -> usize {
+        self.output_cnt
+    }
+
+    /// Estimates the heap memory used by the automation, in bytes.
+    ///
+    /// Only the allocations owned by the automation itself are counted,
+    /// heap data owned by retained patterns is not.
+    pub fn heap_bytes(&self) -> usize {
+        let nodes: usize = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.goto.heap_bytes()
+                    + (node.outputs.capacity() + node.direct_outputs.capacity())
+                        * mem::size_of::<usize>()
+            })
+            .sum();
+
+        let patterns = self
+            .patterns
+            .as_ref()
+            .map_or(0, |patterns| patterns.capacity() * mem::size_of::<P>());
+
+        self.nodes.capacity() * mem::size_of::<AutomationNode<P::Char, S>>()
+            + nodes
+            + (self.pattern_lens.capacity() + self.class_outputs.capacity())
+                * mem::size_of::<usize>()
+            + self.values.capacity() * mem::size_of::<V>()
+            + patterns
+            + map::heap_bytes(&self.duplicates)
+    }
+
+    /// Releases the excess capacity left over from building the automation,
+    /// which is worth it for long-lived automations. Searches are not affected.
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+
+        for node in &mut self.nodes {
+            node.goto.shrink_to_fit();
+            node.outputs.shrink_to_fit();
+            node.direct_outputs.shrink_to_fit();
+        }
+
+        self.pattern_lens.shrink_to_fit();
+        self.class_outputs.shrink_to_fit();
+        self.values.shrink_to_fit();
+        map::shrink_to_fit(&mut self.duplicates);
+
+        if let Some(patterns) = &mut self.patterns {
+            patterns.shrink_to_fit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        let automation = Automation::build(["he", "he", "she"].into_iter());
+        let larger = Automation::build(["he", "he", "she", "hers", "his"].into_iter());
+
+        // Duplicates keep their own output IDs, but share the nodes.
+        assert_eq!(automation.num_patterns(), 3);
+        assert_eq!(automation.num_nodes(), 6);
+        assert!(automation.heap_bytes() >= mem::size_of_val(automation.nodes.as_slice()));
+        assert!(larger.heap_bytes() > automation.heap_bytes());
+    }
+
+    #[test]
+    fn every_node_is_reachable() {
+        let automation = Automation::build(["he", "she", "his", "hers", ""].into_iter());
+
+        assert_eq!(automation.reachable_node_count(), automation.num_nodes());
+        assert_eq!(Automation::<&str>::new().reachable_node_count(), 1);
+    }
+
+    #[test]
+    fn shrink_keeps_matches() {
+        let patterns = ["he", "she", "his", "hers", "a", "ab", "abc"];
+        let haystack = "ushers said his share of hers abcd";
+
+        let mut automation = Automation::build_with_capacity(patterns.into_iter(), 1000);
+        let expected: Vec<_> = automation.find_iter(haystack).collect();
+        let before = automation.heap_bytes();
+
+        automation.shrink_to_fit();
+
+        assert!(automation.heap_bytes() < before);
+        assert_eq!(automation.nodes.capacity(), automation.num_nodes());
+        assert_eq!(automation.find_iter(haystack).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn failure_depth_of_suffix_chain() {
+        let automation = Automation::build(["aaaa", "aaa", "aa", "a"].into_iter());
+        assert_eq!(automation.max_failure_depth(), 4);
+
+        let flat = Automation::build(["ab", "cd"].into_iter());
+        assert_eq!(flat.max_failure_depth(), 1);
+    }
+
+    #[test]
+    fn transitions_of_small_trie() {
+        let automation = Automation::build(["she", "he", "hers"].into_iter());
+        let stats = automation.transition_stats();
+
+        // Root -> s, h; s -> sh -> she; h -> he -> her -> hers.
+        assert_eq!(stats.total, 7);
+        assert_eq!(stats.total, automation.num_nodes() - 1);
+        assert_eq!(stats.max_degree, 2);
+        assert_eq!(stats.avg_degree, 7.0 / 8.0);
+    }
+}
@@ -0,0 +1,282 @@
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{Automation, AutomationSearch, DefaultHashBuilder, HashBuilder, Pattern};
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Iterator over the matches of a byte automation in a [`Read`] stream.
+///
+/// Yields `(pattern_id, end)`, where `end` is the absolute offset in the stream
+/// just past the last byte of the match. The search state is kept between reads,
+/// so matches straddling chunk boundaries are found as well.
+pub struct StreamSearcher<'a, P: Pattern<Char = u8>, R, V = (), S = DefaultHashBuilder> {
+    search: AutomationSearch<'a, P, V, S>,
+    reader: R,
+    buffer: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    offset: u64,
+    outputs: &'a [usize],
+}
+
+impl<'a, P: Pattern<Char = u8>, R: Read, V, S: HashBuilder> StreamSearcher<'a, P, R, V, S> {
+    pub fn new(automation: &'a Automation<P, V, S>, reader: R) -> Self {
+        Self {
+            search: automation.search(),
+            reader,
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            offset: 0,
+            outputs: automation.start_outputs(),
+        }
+    }
+}
+
+impl<P: Pattern<Char = u8>, R: Read, V, S: HashBuilder> Iterator
+    for StreamSearcher<'_, P, R, V, S>
+{
+    type Item = io::Result<(usize, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((&output, rest)) = self.outputs.split_first() {
+                self.outputs = rest;
+                return Some(Ok((output, self.offset)));
+            }
+
+            if self.pos == self.len {
+                match self.reader.read(&mut self.buffer) {
+                    Ok(0) => return None,
+                    Ok(n) => {
+                        self.pos = 0;
+                        self.len = n;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let b = self.buffer[self.pos];
+            self.pos += 1;
+            self.offset += 1;
+            self.outputs = self.search.step(&b);
+        }
+    }
+}
+
+impl<P: Pattern<Char = u8>, V, S: HashBuilder> Automation<P, V, S> {
+    /// Searches a byte stream, reading it in chunks.
+    pub fn search_reader<R: Read>(&self, reader: R) -> StreamSearcher<'_, P, R, V, S> {
+        StreamSearcher::new(self, reader)
+    }
+
+    /// Streaming counterpart of [`Automation::replace_all`]: copies `reader` to `writer`,
+    /// replacing every non-overlapping match with `replacements[pattern_id]`.
+    ///
+    /// Matches are picked the same way as by `replace_all`. Only the bytes of a match
+    /// that may still be in progress are held back, so memory use is bounded
+    /// by the longest pattern, whatever the size of the input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer replacements than patterns.
+    pub fn replace_stream<R: Read, W: Write>(
+        &self,
+        mut reader: R,
+        mut writer: W,
+        replacements: &[&str],
+    ) -> io::Result<()> {
+        assert!(
+            replacements.len() >= self.output_cnt,
+            "expected a replacement for each of {} patterns, got {}",
+            self.output_cnt,
+            replacements.len()
+        );
+
+        let mut search = self.search();
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut out = Vec::with_capacity(BUFFER_SIZE);
+        // Bytes of the current trie path, which may still become part of a match.
+        let mut pending = Vec::new();
+
+        if let Some(output) = self.longest_output(self.start_outputs()) {
+            out.extend_from_slice(replacements[output].as_bytes());
+        }
+
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            for &b in &buffer[..n] {
+                let outputs = search.step(&b);
+                pending.push(b);
+
+                let kept = match self.longest_output(outputs) {
+                    Some(output) => {
+                        let start = pending.len() - self.pattern_lens[output];
+                        out.extend_from_slice(&pending[..start]);
+                        out.extend_from_slice(replacements[output].as_bytes());
+                        search.reset();
+                        0
+                    }
+                    None => {
+                        let kept = search.current_depth();
+                        out.extend_from_slice(&pending[..pending.len() - kept]);
+                        kept
+                    }
+                };
+
+                pending.drain(..pending.len() - kept);
+            }
+
+            writer.write_all(&out)?;
+            out.clear();
+        }
+
+        writer.write_all(&pending)?;
+        writer.flush()
+    }
+}
+
+impl Automation<String> {
+    /// Builds the automation from a newline separated list of patterns,
+    /// e.g. a keyword file. Line endings are stripped and empty lines are skipped.
+    ///
+    /// The patterns are retained, as with [`Automation::build_with_patterns`].
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut patterns = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if !line.is_empty() {
+                patterns.push(line);
+            }
+        }
+
+        Ok(Self::build_with_patterns(patterns.into_iter()))
+    }
+
+    /// Same as [`Automation::from_reader`], but does not retain the patterns.
+    ///
+    /// Lines are inserted one by one as they are read, so only the trie stays
+    /// in memory, not the list of patterns, which allows building from pattern files
+    /// larger than the memory.
+    pub fn build_from_lines<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut automation = Self::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if !line.is_empty() {
+                automation.add_item(line);
+            }
+        }
+        automation.finalize();
+
+        Ok(automation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out at most `chunk` bytes per read, so matches straddle the reads.
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn replace_stream_matches_replace_all() {
+        let patterns = ["he", "she", "hers", "his", "s"];
+        let replacements = ["1", "22", "333", "", "s!"];
+        let haystack = "ushers said his share of hershey hisses was hers";
+
+        let expected = Automation::build(patterns.into_iter()).replace_all(haystack, &replacements);
+        let automation = Automation::build(patterns.iter().map(|pattern| pattern.as_bytes()));
+
+        for chunk in 1..=7 {
+            let reader = Chunked {
+                data: haystack.as_bytes(),
+                chunk,
+            };
+            let mut out = Vec::new();
+            automation
+                .replace_stream(reader, &mut out, &replacements)
+                .unwrap();
+
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                expected,
+                "chunk size {}",
+                chunk
+            );
+        }
+    }
+
+    #[test]
+    fn search_reader_across_single_byte_reads() {
+        let automation = Automation::build([&b"he"[..], b"she", b"hers"].into_iter());
+        let haystack = b"ushers and hers";
+        let expected: Vec<_> = automation
+            .find_iter_bytes(haystack)
+            .map(|(output, end)| (output, end as u64))
+            .collect();
+
+        let reader = Chunked {
+            data: haystack,
+            chunk: 1,
+        };
+        let matches: Vec<_> = automation
+            .search_reader(reader)
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(matches, [(1, 4), (0, 4), (2, 6), (0, 13), (2, 15)]);
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn from_reader_skips_empty_lines() {
+        let automation = Automation::from_reader("he\r\nshe\n\n\r\nhers\n".as_bytes()).unwrap();
+
+        assert_eq!(automation.num_patterns(), 3);
+        assert_eq!(automation.pattern(0), "he");
+        assert_eq!(automation.pattern(2), "hers");
+        assert_eq!(automation.find_iter("ushers").count(), 3);
+    }
+
+    #[test]
+    fn build_from_many_lines() {
+        let mut source = String::new();
+        for i in 0..10_000 {
+            if i % 100 == 0 {
+                source.push('\n');
+            }
+            source.push_str(&alloc::format!("kw{}\n", i));
+        }
+
+        let automation = Automation::build_from_lines(source.as_bytes()).unwrap();
+
+        assert_eq!(automation.num_patterns(), 10_000);
+        assert_eq!(automation.find_iter("kw9999").last(), Some((9999, 6)));
+        assert!(!automation.is_match("k9"));
+    }
+}
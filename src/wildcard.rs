@@ -0,0 +1,244 @@
+use alloc::{vec, vec::Vec};
+use core::marker::PhantomData;
+
+use crate::{map::Map, Automation, AutomationSearch, Pattern};
+
+/// Automation for patterns containing wildcards, which match any single char.
+///
+/// The parts of the patterns between wildcards are searched with a regular
+/// [`Automation`]. A pattern matches once all of its parts were found at the right
+/// distances from each other, which is tracked by counting, for every possible
+/// start, how many of the parts were seen.
+pub struct WildcardAutomation<P: Pattern>
+where
+    P::Char: Clone,
+{
+    segments: Automation<Vec<P::Char>>,
+    // For every segment, the patterns containing it,
+    // each with the offset just past the segment in the pattern.
+    uses: Vec<Vec<(usize, usize)>>,
+    segment_cnts: Vec<usize>,
+    pattern_lens: Vec<usize>,
+    // Patterns made of wildcards only, they match wherever enough chars were fed.
+    wildcards_only: Vec<usize>,
+    window: usize,
+    pattern: PhantomData<P>,
+}
+
+impl<P: Pattern> WildcardAutomation<P>
+where
+    P::Char: Clone,
+{
+    /// Builds the automation from the given patterns, in which every `wildcard` char
+    /// matches any single char. Output IDs are assigned in the order the patterns come in.
+    pub fn build(items: impl Iterator<Item = P>, wildcard: P::Char) -> Self {
+        let mut segment_ids: Map<Vec<P::Char>, usize> = Map::new();
+        let mut segments = Vec::new();
+        let mut uses: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut segment_cnts = Vec::new();
+        let mut pattern_lens = Vec::new();
+        let mut wildcards_only = Vec::new();
+
+        for (id, item) in items.enumerate() {
+            let chars: Vec<P::Char> = item.iter().collect();
+            let mut cnt = 0;
+
+            for (end, segment) in split_segments(&chars, &wildcard) {
+                let segment_id = *segment_ids.entry(segment.to_vec()).or_insert_with(|| {
+                    segments.push(segment.to_vec());
+                    uses.push(Vec::new());
+                    segments.len() - 1
+                });
+
+                uses[segment_id].push((id, end));
+                cnt += 1;
+            }
+
+            if cnt == 0 {
+                wildcards_only.push(id);
+            }
+
+            segment_cnts.push(cnt);
+            pattern_lens.push(chars.len());
+        }
+
+        let window = pattern_lens
+            .as_slice()
+            .iter()
+            .max()
+            .map_or(1, |&len| len + 1);
+
+        Self {
+            segments: Automation::build(segments.into_iter()),
+            uses,
+            segment_cnts,
+            pattern_lens,
+            wildcards_only,
+            window,
+            pattern: PhantomData,
+        }
+    }
+}
+
+impl<P: Pattern> WildcardAutomation<P>
+where
+    P::Char: Clone,
+{
+    /// Returns the length of the pattern with output ID `id`, in `Char`s, wildcards included.
+    pub fn pattern_len(&self, id: usize) -> usize {
+        self.pattern_lens[id]
+    }
+
+    pub fn search(&self) -> WildcardSearch<'_, P> {
+        WildcardSearch::new(self)
+    }
+}
+
+impl<P: Pattern<Char = char>> WildcardAutomation<P> {
+    /// Yields `(pattern_id, end)` for every overlapping match in `haystack`,
+    /// where `end` is the byte offset just past the last char of the match.
+    pub fn find_iter<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut search = self.search();
+        let mut chars = haystack.char_indices();
+        let mut reported = 0;
+        let mut end = 0;
+
+        // Empty patterns also match before the first char.
+        let start = self
+            .wildcards_only
+            .as_slice()
+            .iter()
+            .filter(|&&id| self.pattern_lens[id] == 0)
+            .map(|&id| (id, 0));
+
+        start.chain(core::iter::from_fn(move || loop {
+            if let Some(&output) = search.outputs.get(reported) {
+                reported += 1;
+                return Some((output, end));
+            }
+
+            let (i, c) = chars.next()?;
+            search.next(&c);
+            reported = 0;
+            end = i + c.len_utf8();
+        }))
+    }
+}
+
+// Yields the maximal runs of `chars` without wildcards,
+// each with the offset just past it.
+fn split_segments<'a, C: PartialEq>(
+    chars: &'a [C],
+    wildcard: &'a C,
+) -> impl Iterator<Item = (usize, &'a [C])> + 'a {
+    let mut start = 0;
+
+    chars
+        .split(move |c| c == wildcard)
+        .filter_map(move |segment| {
+            let end = start + segment.len();
+            start = end + 1;
+            (!segment.is_empty()).then_some((end, segment))
+        })
+}
+
+pub struct WildcardSearch<'a, P: Pattern>
+where
+    P::Char: Clone,
+{
+    automation: &'a WildcardAutomation<P>,
+    search: AutomationSearch<'a, Vec<P::Char>>,
+    // Number of chars fed so far.
+    fed: usize,
+    // For every end offset in the window, modulo its size, how many segments
+    // of each pattern ending there were found, and which patterns are complete.
+    counts: Vec<Map<usize, usize>>,
+    complete: Vec<Vec<usize>>,
+    outputs: Vec<usize>,
+}
+
+impl<'a, P: Pattern> WildcardSearch<'a, P>
+where
+    P::Char: Clone,
+{
+    pub fn new(automation: &'a WildcardAutomation<P>) -> Self {
+        Self {
+            automation,
+            search: automation.segments.search(),
+            fed: 0,
+            counts: (0..automation.window).map(|_| Map::new()).collect(),
+            complete: vec![Vec::new(); automation.window],
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Feeds the next char and returns the outputs of all patterns ending at it.
+    pub fn next(&mut self, c: &P::Char) -> &[usize] {
+        let automation = self.automation;
+        let folded = P::fold(c);
+        let c = folded.as_ref().unwrap_or(c);
+
+        self.fed += 1;
+        self.outputs.clear();
+
+        for &segment in self.search.next(c) {
+            for &(id, end) in &automation.uses[segment] {
+                // The pattern would have to start before the input.
+                let Some(start) = self.fed.checked_sub(end) else {
+                    continue;
+                };
+
+                let pattern_end = start + automation.pattern_lens[id];
+                let slot = pattern_end % automation.window;
+                let cnt = self.counts[slot].entry(id).or_insert(0);
+                *cnt += 1;
+
+                if *cnt == automation.segment_cnts[id] {
+                    self.complete[slot].push(id);
+                }
+            }
+        }
+
+        for &id in &automation.wildcards_only {
+            if automation.pattern_lens[id] <= self.fed {
+                self.outputs.push(id);
+            }
+        }
+
+        let slot = self.fed % automation.window;
+        self.outputs.append(&mut self.complete[slot]);
+        self.counts[slot].clear();
+
+        &self.outputs
+    }
+
+    /// Returns the search to its initial state, as if no chars were fed yet.
+    pub fn reset(&mut self) {
+        self.search.reset();
+        self.fed = 0;
+        self.outputs.clear();
+
+        for (counts, complete) in self.counts.iter_mut().zip(&mut self.complete) {
+            counts.clear();
+            complete.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_matches_any_char() {
+        let automation = WildcardAutomation::build(["h?rs"].into_iter(), '?');
+
+        assert_eq!(automation.find_iter("hers").collect::<Vec<_>>(), [(0, 4)]);
+        assert_eq!(automation.find_iter("hars").collect::<Vec<_>>(), [(0, 4)]);
+        assert_eq!(automation.find_iter("hrs").count(), 0);
+        assert_eq!(
+            automation.find_iter("ahhhrsa").collect::<Vec<_>>(),
+            [(0, 6)]
+        );
+    }
+}